@@ -3,6 +3,7 @@
 use bencher::{benchmark_group, benchmark_main, black_box, Bencher};
 use frappe::Sink;
 use rand::prelude::*;
+use std::thread;
 
 /// First-order benchmark.
 ///
@@ -47,6 +48,43 @@ fn first_order_10k(b: &mut Bencher) {
     first_order(1_000, 10_000, b);
 }
 
+/// Same as `first_order`, but the steps are split between `n_threads` threads that send
+/// concurrently to the same sinks.
+fn first_order_threads(n_sinks: usize, n_steps: usize, n_threads: usize, b: &mut Bencher) {
+    // Setup network
+    let sinks: Vec<Sink<String>> = (0..n_sinks).map(|_| Sink::new()).collect();
+    let _printers: Vec<_> = sinks
+        .iter()
+        .map(|sink| {
+            sink.stream().map(|s| {
+                black_box(format!("{}", s));
+            })
+        })
+        .collect();
+
+    // Feed events
+    b.iter(|| {
+        thread::scope(|scope| {
+            for t in 0..n_threads {
+                let sinks = &sinks;
+                scope.spawn(move || {
+                    let mut rng = rand::thread_rng();
+                    for k in (t..n_steps).step_by(n_threads) {
+                        let s = format!("{}", k);
+                        for sink in sinks.choose_multiple(&mut rng, 10) {
+                            sink.send(s.clone());
+                        }
+                    }
+                });
+            }
+        });
+    });
+}
+
+fn first_order_1k_4threads(b: &mut Bencher) {
+    first_order_threads(1_000, 1_000, 4, b);
+}
+
 /// A small reference benchmark to do the same amount of actual work without FRP
 fn first_order_1k_ref(b: &mut Bencher) {
     let mut rng = rand::thread_rng();
@@ -65,6 +103,7 @@ benchmark_group!(
     first_order_100,
     first_order_1k,
     first_order_10k,
+    first_order_1k_4threads,
     first_order_1k_ref
 );
 benchmark_main!(first_order_);
@@ -3,6 +3,7 @@
 use bencher::{benchmark_group, benchmark_main, Bencher};
 use frappe::Sink;
 use rand::prelude::*;
+use std::thread;
 
 /// Second-order benchmark.
 ///
@@ -42,6 +43,47 @@ fn second_order(n_sinks: usize, n_steps: usize, b: &mut Bencher) {
     });
 }
 
+/// Same as `second_order`, but the steps are split between `n_threads` threads that send
+/// concurrently to the same sinks.
+fn second_order_threads(n_sinks: usize, n_steps: usize, n_threads: usize, b: &mut Bencher) {
+    // Setup network
+    let stepper = Sink::<usize>::new();
+    let sinks: Vec<_> = (0..n_sinks).map(|_| Sink::new()).collect();
+    let counters: Vec<_> = sinks
+        .iter()
+        .map(|sink| sink.stream().fold(0, |n, _| n + 1))
+        .collect();
+    let walker = {
+        let counters = counters.clone();
+        stepper.stream().map(move |k| counters[*k / 10].clone())
+    };
+    let signal = walker.hold(counters[0].clone()).switch();
+
+    // Feed events
+    b.iter(|| {
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..n_threads)
+                .map(|t| {
+                    let (stepper, sinks, signal) = (&stepper, &sinks, &signal);
+                    scope.spawn(move || {
+                        let mut rng = rand::thread_rng();
+                        let mut res = 0;
+                        for i in (t..n_steps).step_by(n_threads) {
+                            stepper.send(i);
+                            for sink in sinks.choose_multiple(&mut rng, 10) {
+                                sink.send(());
+                            }
+                            res += signal.sample();
+                        }
+                        res
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum::<i32>()
+        })
+    });
+}
+
 fn second_order_100(b: &mut Bencher) {
     second_order(1_000, 100, b);
 }
@@ -54,10 +96,15 @@ fn second_order_10k(b: &mut Bencher) {
     second_order(1_000, 10_000, b);
 }
 
+fn second_order_1k_4threads(b: &mut Bencher) {
+    second_order_threads(1_000, 1_000, 4, b);
+}
+
 benchmark_group!(
     second_order_,
     second_order_100,
     second_order_1k,
-    second_order_10k
+    second_order_10k,
+    second_order_1k_4threads
 );
 benchmark_main!(second_order_);
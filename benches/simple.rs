@@ -35,5 +35,26 @@ fn send_and_sample(b: &mut Bencher) {
     });
 }
 
-benchmark_group!(simple, send, sample, send_and_sample);
+fn sample_hold(b: &mut Bencher) {
+    let sink = Sink::new();
+    let sig = sink.stream().hold(0u64);
+    sink.send(42);
+    b.iter(|| sig.sample());
+}
+
+fn sample_hold_atomic(b: &mut Bencher) {
+    let sink = Sink::new();
+    let sig = sink.stream().hold_atomic(0u64);
+    sink.send(42);
+    b.iter(|| sig.sample());
+}
+
+benchmark_group!(
+    simple,
+    send,
+    sample,
+    send_and_sample,
+    sample_hold,
+    sample_hold_atomic
+);
 benchmark_main!(simple);
@@ -1,4 +1,6 @@
+use frappe::futures::{BoxFuture, Concurrency};
 use frappe::Sink;
+use futures::executor::block_on;
 use rand::distributions::Uniform;
 use rand::Rng;
 use std::thread;
@@ -7,19 +9,25 @@ use std::time::Duration;
 fn main() {
     let sink = Sink::new();
 
+    // each future runs on it's own thread
+    let spawner = |future: BoxFuture| {
+        thread::spawn(move || block_on(future));
+    };
+
     // build the chain on the main thread
     let result = sink
         .stream()
-        // we'll do this part on another thread
-        .map_n(|arg, sender| {
-            let n = *arg;
-            // our expensive computation (sleep sort)
-            thread::spawn(move || {
+        // we'll do this part on other threads
+        .map_async(
+            |n: u64| async move {
+                // our expensive computation (sleep sort)
                 thread::sleep(Duration::from_millis(n));
-                sender.send(n); // return the computed value
-            });
-        })
-        // the rest of the stream chain is executed on the thread that called `Sender::send`
+                n
+            },
+            Concurrency::Unordered(10),
+            spawner,
+        )
+        // the rest of the stream chain is executed on the thread that completed the future
         .fold(Vec::new(), |mut vec, n| {
             vec.push(*n);
             vec
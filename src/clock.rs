@@ -0,0 +1,276 @@
+//! Clock abstraction for time dependent operations.
+//!
+//! The time is measured as a `Duration` from an arbitrary starting point (the clock's epoch).
+//! `SystemClock` uses the real time, and `ManualClock` only moves forward when it's told to, so
+//! time dependent code can be tested deterministically.
+//!
+//! Operations that need to act later (like `StreamFuture::timeout`) use a `Scheduler` instead, so
+//! they don't block the thread that sends the events.
+//!
+//! # Example
+//! ```
+//! use frappe::clock::{Clock, ManualClock};
+//! use std::time::Duration;
+//!
+//! let clock = ManualClock::new();
+//! clock.advance(Duration::from_secs(1));
+//! clock.sleep_until(Duration::from_secs(3));
+//! assert_eq!(clock.now(), Duration::from_secs(3));
+//! ```
+
+use crate::sync::Mutex;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A source of time.
+pub trait Clock: Send + Sync {
+    /// Gets the time elapsed since the clock's epoch.
+    fn now(&self) -> Duration;
+
+    /// Blocks the current thread until `now()` reaches the specified time.
+    fn sleep_until(&self, deadline: Duration);
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    #[inline]
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+
+    #[inline]
+    fn sleep_until(&self, deadline: Duration) {
+        (**self).sleep_until(deadline)
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    #[inline]
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+
+    #[inline]
+    fn sleep_until(&self, deadline: Duration) {
+        (**self).sleep_until(deadline)
+    }
+}
+
+/// A clock that uses the system's monotonic time.
+///
+/// It's epoch is the moment when it was created.
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    /// Creates a clock that starts at the current time.
+    #[inline]
+    pub fn new() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    /// Creates a clock that starts at the current time.
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep_until(&self, deadline: Duration) {
+        let now = self.now();
+        if deadline > now {
+            thread::sleep(deadline - now);
+        }
+    }
+}
+
+/// A clock that is advanced manually.
+///
+/// Sleeping on this clock doesn't block, it just moves the clock forward to the deadline.
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: Mutex<Duration>,
+}
+
+impl ManualClock {
+    /// Creates a clock that starts at zero.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Moves the clock forward.
+    pub fn advance(&self, delta: Duration) {
+        *self.now.lock() += delta;
+    }
+
+    /// Sets the current time.
+    pub fn set(&self, now: Duration) {
+        *self.now.lock() = now;
+    }
+}
+
+impl Clock for ManualClock {
+    #[inline]
+    fn now(&self) -> Duration {
+        *self.now.lock()
+    }
+
+    fn sleep_until(&self, deadline: Duration) {
+        let mut now = self.now.lock();
+        if deadline > *now {
+            *now = deadline;
+        }
+    }
+}
+
+/// A task run by a `Scheduler`.
+pub type Task = Box<dyn FnOnce() + Send>;
+
+/// Runs tasks after a delay.
+///
+/// It's used by the time dependent operations, like `StreamFuture::timeout`. The tasks can be run
+/// on any thread.
+pub trait Scheduler: Send + Sync {
+    /// Runs a task after `delay` has elapsed.
+    fn schedule(&self, delay: Duration, task: Task);
+}
+
+impl<S: Scheduler + ?Sized> Scheduler for &S {
+    #[inline]
+    fn schedule(&self, delay: Duration, task: Task) {
+        (**self).schedule(delay, task)
+    }
+}
+
+impl<S: Scheduler + ?Sized> Scheduler for Arc<S> {
+    #[inline]
+    fn schedule(&self, delay: Duration, task: Task) {
+        (**self).schedule(delay, task)
+    }
+}
+
+/// A scheduler that runs every task on a new thread after sleeping.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadScheduler;
+
+impl Scheduler for ThreadScheduler {
+    fn schedule(&self, delay: Duration, task: Task) {
+        thread::spawn(move || {
+            thread::sleep(delay);
+            task()
+        });
+    }
+}
+
+/// A scheduler that uses the timers of a tokio runtime.
+///
+/// The tasks are spawned on the runtime and wait with `tokio::time::sleep`, so they follow the
+/// paused test clock.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone)]
+pub struct TokioScheduler {
+    handle: tokio::runtime::Handle,
+}
+
+#[cfg(feature = "tokio")]
+impl TokioScheduler {
+    /// Creates a scheduler that uses the current runtime.
+    ///
+    /// Panics if it's not called from inside a tokio runtime.
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_handle(tokio::runtime::Handle::current())
+    }
+
+    /// Creates a scheduler that uses the specified runtime.
+    #[inline]
+    pub fn with_handle(handle: tokio::runtime::Handle) -> Self {
+        TokioScheduler { handle }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Default for TokioScheduler {
+    /// Creates a scheduler that uses the current runtime.
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Scheduler for TokioScheduler {
+    fn schedule(&self, delay: Duration, task: Task) {
+        self.handle.spawn(async move {
+            tokio::time::sleep(delay).await;
+            task()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_system() {
+        let clock = SystemClock::new();
+        let t0 = clock.now();
+        clock.sleep_until(t0 + Duration::from_millis(20));
+        assert!(clock.now() >= t0 + Duration::from_millis(20));
+        // deadlines in the past return immediately
+        clock.sleep_until(t0);
+    }
+
+    #[test]
+    fn clock_manual() {
+        let clock = ManualClock::new();
+        assert_eq!(clock.now(), Duration::from_secs(0));
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(clock.now(), Duration::from_millis(1500));
+        clock.sleep_until(Duration::from_secs(1));
+        assert_eq!(clock.now(), Duration::from_millis(1500));
+        clock.sleep_until(Duration::from_secs(2));
+        assert_eq!(clock.now(), Duration::from_secs(2));
+        clock.set(Duration::from_secs(1));
+        assert_eq!(clock.now(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn scheduler_thread() {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let t = Instant::now();
+        ThreadScheduler.schedule(
+            Duration::from_millis(20),
+            Box::new(move || tx.send(1).unwrap()),
+        );
+        assert_eq!(rx.recv(), Ok(1));
+        assert!(t.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn scheduler_tokio() {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let t = tokio::time::Instant::now();
+        TokioScheduler::new().schedule(
+            Duration::from_secs(60),
+            Box::new(move || tx.send(1).unwrap()),
+        );
+        assert_eq!(rx.await, Ok(1));
+        assert_eq!(t.elapsed(), Duration::from_secs(60));
+    }
+}
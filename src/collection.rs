@@ -0,0 +1,188 @@
+//! Collection signals that report their changes as diffs.
+//!
+//! `Stream::collect` produces a `Signal` that clones the whole collection every time it's
+//! sampled, so keeping something like a list widget in sync requires comparing the full
+//! collection. The types in this module instead send a diff event for every change made to the
+//! collection, so it can be synced incrementally.
+//!
+//! - `MutableVec` is a vector that can be modified, and `SignalVec` is a read-only view of it that
+//!   sends `VecDiff` events.
+//! - `MutableMap` is an ordered map that can be modified, and `SignalMap` is a read-only view of it
+//!   that sends `MapDiff` events.
+//!
+//! The operators on the read-only views (like `SignalVec::map` or `SignalVec::filter`) create
+//! new views that are updated by applying the diffs, without processing the whole collection.
+//!
+//! # Example
+//! ```
+//! use frappe::collection::{MutableVec, VecDiff};
+//!
+//! let todos = MutableVec::<(&str, bool)>::new();
+//! let pending = todos.signal_vec().filter(|&(_, done)| !done);
+//! let diffs = pending.diffs().collect::<Vec<_>>();
+//!
+//! todos.push(("buy milk", false));
+//! todos.push(("walk dog", true));
+//! todos.set(0, ("buy milk", true));
+//!
+//! assert!(pending.is_empty());
+//! assert_eq!(
+//!     diffs.sample(),
+//!     [
+//!         VecDiff::Insert { index: 0, value: ("buy milk", false) },
+//!         VecDiff::Remove { index: 0 },
+//!     ]
+//! );
+//! ```
+
+mod map;
+mod vec;
+
+pub use self::map::{MapDiff, MutableMap, SignalMap};
+pub use self::vec::{MutableVec, SignalVec, VecDiff};
+
+use crate::stream::{Sink, Stream};
+use crate::sync::{Mutex, RwLock};
+use crate::types::MaybeOwned;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// A diff tagged with the version of the collection it produces.
+type Versioned<D> = (u64, D);
+
+/// A collection and it's version, that is incremented on every change.
+#[derive(Debug)]
+struct Data<C> {
+    version: u64,
+    values: C,
+}
+
+/// The state shared by a mutable collection and it's views.
+pub(crate) struct Shared<C, D> {
+    data: RwLock<Data<C>>,
+    /// Diffs waiting to be sent, in the order they were applied.
+    pending: Mutex<VecDeque<Versioned<D>>>,
+    /// Set while a thread is sending the pending diffs.
+    sending: AtomicBool,
+    sink: Sink<Versioned<D>>,
+    /// Keeps alive the collection or stream this one is derived from.
+    _source: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl<C, D> Shared<C, D>
+where
+    C: Send + Sync + 'static,
+    D: Send + Sync + 'static,
+{
+    /// Creates a collection with initial values.
+    pub fn new(values: C, source: Option<Arc<dyn Any + Send + Sync>>) -> Arc<Self> {
+        Arc::new(Shared {
+            data: RwLock::new(Data { version: 0, values }),
+            pending: Default::default(),
+            sending: AtomicBool::new(false),
+            sink: Sink::new(),
+            _source: source,
+        })
+    }
+
+    /// Reads the collection.
+    pub fn read<R>(&self, f: impl FnOnce(&C) -> R) -> R {
+        f(&self.data.read().values)
+    }
+
+    /// Modifies the collection.
+    ///
+    /// The closure must push the diffs for the changes it makes. The diffs are sent after the
+    /// collection is unlocked, so the observers can read it.
+    pub fn mutate<R>(&self, f: impl FnOnce(&mut C, &mut Vec<D>) -> R) -> R {
+        let mut diffs = Vec::new();
+        let result = {
+            let mut data = self.data.write();
+            let result = f(&mut data.values, &mut diffs);
+            if !diffs.is_empty() {
+                // queued while holding the lock, so they're sent in the same order they were applied
+                let mut pending = self.pending.lock();
+                for diff in diffs {
+                    data.version += 1;
+                    pending.push_back((data.version, diff));
+                }
+            }
+            result
+        };
+        self.flush();
+        result
+    }
+
+    /// Sends the pending diffs.
+    ///
+    /// Only one thread sends at a time. If another thread (or an observer, from inside a diff
+    /// callback) is already sending, the diffs are left for it to send.
+    fn flush(&self) {
+        while !self.sending.swap(true, Ordering::SeqCst) {
+            loop {
+                let next = self.pending.lock().pop_front();
+                match next {
+                    Some(diff) => self.sink.send(diff),
+                    None => break,
+                }
+            }
+            self.sending.store(false, Ordering::SeqCst);
+            // someone could have queued more diffs after we found the queue empty
+            if self.pending.lock().is_empty() {
+                break;
+            }
+        }
+    }
+
+    /// Gets a stream of the diffs.
+    pub fn diffs(&self) -> Stream<D>
+    where
+        D: Clone,
+    {
+        self.sink.stream().map(|diff| match diff {
+            MaybeOwned::Owned((_, diff)) => diff,
+            MaybeOwned::Borrowed((_, diff)) => diff.clone(),
+        })
+    }
+
+    /// Creates a collection that is updated with the diffs of this one.
+    ///
+    /// `init` builds the initial values and the operator state from the current values, and `f`
+    /// applies the diffs to the new collection, pushing the resulting diffs.
+    pub fn derive<C2, D2, S, I, F>(self: &Arc<Self>, init: I, f: F) -> Arc<Shared<C2, D2>>
+    where
+        C2: Send + Sync + 'static,
+        D2: Send + Sync + 'static,
+        S: Send + 'static,
+        I: FnOnce(&C) -> (C2, S),
+        F: Fn(&mut S, &D, &mut C2, &mut Vec<D2>) + Send + Sync + 'static,
+    {
+        // the lock prevents changes until we're subscribed to the diffs
+        let data = self.data.read();
+        let (values, state) = init(&data.values);
+        let version = data.version;
+        let child = Shared::new(values, Some(self.clone() as Arc<dyn Any + Send + Sync>));
+        let weak = Arc::downgrade(&child);
+        let state = Mutex::new(state);
+        self.sink.stream().observe(move |diff| {
+            with_weak!(weak, |child| {
+                // skip the diffs that were already applied when we took the initial values
+                if diff.0 > version {
+                    let mut state = state.lock();
+                    child.mutate(|values, out| f(&mut state, &diff.1, values, out));
+                }
+            })
+        });
+        child
+    }
+}
+
+impl<C: fmt::Debug, D> fmt::Debug for Shared<C, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shared").field("data", &self.data).finish()
+    }
+}
@@ -0,0 +1,409 @@
+//! Map signal.
+
+use crate::collection::Shared;
+use crate::signal::Signal;
+use crate::stream::Stream;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A change made to a map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapDiff<K, V> {
+    /// All the entries were replaced.
+    Replace {
+        /// The new entries, sorted by key.
+        entries: Vec<(K, V)>,
+    },
+    /// A new entry was inserted.
+    Insert {
+        /// Key of the entry.
+        key: K,
+        /// The value inserted.
+        value: V,
+    },
+    /// The value of an existing entry was replaced.
+    Update {
+        /// Key of the entry.
+        key: K,
+        /// The new value.
+        value: V,
+    },
+    /// An entry was removed.
+    Remove {
+        /// Key of the entry.
+        key: K,
+    },
+    /// All the entries were removed.
+    Clear,
+}
+
+impl<K: Ord, V> MapDiff<K, V> {
+    /// Applies this change to a map.
+    pub fn apply(self, map: &mut BTreeMap<K, V>) {
+        match self {
+            MapDiff::Replace { entries } => *map = entries.into_iter().collect(),
+            MapDiff::Insert { key, value } | MapDiff::Update { key, value } => {
+                map.insert(key, value);
+            }
+            MapDiff::Remove { key } => {
+                map.remove(&key);
+            }
+            MapDiff::Clear => map.clear(),
+        }
+    }
+}
+
+/// Applies a diff to a derived map and sends it.
+fn emit<K, V>(map: &mut BTreeMap<K, V>, out: &mut Vec<MapDiff<K, V>>, diff: MapDiff<K, V>)
+where
+    K: Ord + Clone,
+    V: Clone,
+{
+    diff.clone().apply(map);
+    out.push(diff);
+}
+
+type SharedMap<K, V> = Shared<BTreeMap<K, V>, MapDiff<K, V>>;
+
+/// An ordered map that sends a diff event on every change.
+///
+/// Clones of this object share the same map. Use `MutableMap::signal_map` to get a read-only view
+/// of it.
+pub struct MutableMap<K, V> {
+    shared: Arc<SharedMap<K, V>>,
+}
+
+impl<K, V> MutableMap<K, V>
+where
+    K: Ord + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Creates an empty map.
+    #[inline]
+    pub fn new() -> Self {
+        Self::from(BTreeMap::new())
+    }
+
+    /// Gets a read-only view of this map.
+    #[inline]
+    pub fn signal_map(&self) -> SignalMap<K, V> {
+        SignalMap {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Inserts a value, returning the previous value for the same key.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.shared.mutate(|map, out| {
+            let old = map.insert(key.clone(), value.clone());
+            out.push(match old {
+                Some(_) => MapDiff::Update { key, value },
+                None => MapDiff::Insert { key, value },
+            });
+            old
+        })
+    }
+
+    /// Removes a key, returning it's value if it was in the map.
+    pub fn remove(&self, key: &K) -> Option<V> {
+        self.shared.mutate(|map, out| {
+            let old = map.remove(key)?;
+            out.push(MapDiff::Remove { key: key.clone() });
+            Some(old)
+        })
+    }
+
+    /// Removes all the entries.
+    pub fn clear(&self) {
+        self.shared
+            .mutate(|map, out| emit(map, out, MapDiff::Clear))
+    }
+
+    /// Replaces all the entries.
+    pub fn replace(&self, map: BTreeMap<K, V>) {
+        self.shared.mutate(|old, out| {
+            out.push(MapDiff::Replace {
+                entries: map.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            });
+            *old = map;
+        })
+    }
+}
+
+impl<K, V> From<BTreeMap<K, V>> for MutableMap<K, V>
+where
+    K: Ord + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Creates a map with initial entries.
+    #[inline]
+    fn from(map: BTreeMap<K, V>) -> Self {
+        MutableMap {
+            shared: Shared::new(map, None),
+        }
+    }
+}
+
+impl<K, V> Default for MutableMap<K, V>
+where
+    K: Ord + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Creates an empty map.
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Clone for MutableMap<K, V> {
+    /// Creates a copy of this object that shares the same map.
+    #[inline]
+    fn clone(&self) -> Self {
+        MutableMap {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for MutableMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MutableMap").field(&self.shared).finish()
+    }
+}
+
+/// A read-only ordered map that sends a diff event on every change.
+pub struct SignalMap<K, V> {
+    shared: Arc<SharedMap<K, V>>,
+}
+
+impl<K, V> SignalMap<K, V>
+where
+    K: Ord + Clone + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// Gets the amount of entries.
+    pub fn len(&self) -> usize {
+        self.shared.read(BTreeMap::len)
+    }
+
+    /// Checks if the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.shared.read(BTreeMap::is_empty)
+    }
+
+    /// Gets a copy of the value for `key`.
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.shared.read(|map| map.get(key).cloned())
+    }
+
+    /// Checks if the map contains `key`.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.shared.read(|map| map.contains_key(key))
+    }
+
+    /// Gets a copy of all the entries.
+    pub fn to_map(&self) -> BTreeMap<K, V> {
+        self.shared.read(BTreeMap::clone)
+    }
+
+    /// Reads the entries without copying them.
+    ///
+    /// The map is locked while the closure runs, so it must not modify it.
+    pub fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&BTreeMap<K, V>) -> R,
+    {
+        self.shared.read(f)
+    }
+
+    /// Gets a stream of the changes made to the map.
+    #[inline]
+    pub fn diffs(&self) -> Stream<MapDiff<K, V>> {
+        self.shared.diffs()
+    }
+
+    /// Gets a signal that samples a copy of the map.
+    pub fn signal(&self) -> Signal<BTreeMap<K, V>> {
+        let shared = self.shared.clone();
+        Signal::from_fn(move || shared.read(BTreeMap::clone))
+    }
+
+    /// Creates a map with the values transformed by a function.
+    pub fn map_values<F, R>(&self, f: F) -> SignalMap<K, R>
+    where
+        F: Fn(&K, &V) -> R + Send + Sync + 'static,
+        R: Clone + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        let f_init = f.clone();
+        let shared = self.shared.derive(
+            move |map| {
+                let map = map.iter().map(|(k, v)| (k.clone(), f_init(k, v)));
+                (map.collect(), ())
+            },
+            move |_, diff, map, out| {
+                let diff = match diff {
+                    MapDiff::Replace { entries } => MapDiff::Replace {
+                        entries: entries.iter().map(|(k, v)| (k.clone(), f(k, v))).collect(),
+                    },
+                    MapDiff::Insert { key, value } => MapDiff::Insert {
+                        key: key.clone(),
+                        value: f(key, value),
+                    },
+                    MapDiff::Update { key, value } => MapDiff::Update {
+                        key: key.clone(),
+                        value: f(key, value),
+                    },
+                    MapDiff::Remove { key } => MapDiff::Remove { key: key.clone() },
+                    MapDiff::Clear => MapDiff::Clear,
+                };
+                emit(map, out, diff)
+            },
+        );
+        SignalMap { shared }
+    }
+
+    /// Creates a map with only the entries where the predicate is `true`.
+    pub fn filter<F>(&self, pred: F) -> SignalMap<K, V>
+    where
+        F: Fn(&K, &V) -> bool + Send + Sync + 'static,
+    {
+        let pred = Arc::new(pred);
+        let pred_init = pred.clone();
+        let shared = self.shared.derive(
+            move |map| {
+                let map = map.iter().filter(|(k, v)| pred_init(k, v));
+                (map.map(|(k, v)| (k.clone(), v.clone())).collect(), ())
+            },
+            move |_, diff, map, out| match diff {
+                MapDiff::Replace { entries } => {
+                    let entries = entries.iter().filter(|(k, v)| pred(k, v)).cloned();
+                    let entries = entries.collect();
+                    emit(map, out, MapDiff::Replace { entries })
+                }
+                MapDiff::Insert { key, value } | MapDiff::Update { key, value } => {
+                    let (key, value) = (key.clone(), value.clone());
+                    match (map.contains_key(&key), pred(&key, &value)) {
+                        (true, true) => emit(map, out, MapDiff::Update { key, value }),
+                        (true, false) => emit(map, out, MapDiff::Remove { key }),
+                        (false, true) => emit(map, out, MapDiff::Insert { key, value }),
+                        (false, false) => (),
+                    }
+                }
+                MapDiff::Remove { key } => {
+                    if map.contains_key(key) {
+                        emit(map, out, MapDiff::Remove { key: key.clone() })
+                    }
+                }
+                MapDiff::Clear => emit(map, out, MapDiff::Clear),
+            },
+        );
+        SignalMap { shared }
+    }
+}
+
+impl<K, V> Clone for SignalMap<K, V> {
+    /// Creates a copy of this object that shares the same map.
+    #[inline]
+    fn clone(&self) -> Self {
+        SignalMap {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for SignalMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SignalMap").field(&self.shared).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    use alloc::vec;
+
+    #[test]
+    fn map_mutate() {
+        let map = MutableMap::new();
+        let view = map.signal_map();
+        let diffs = view.diffs().collect::<Vec<_>>();
+        let synced = view.diffs().fold(BTreeMap::new(), |mut m, diff| {
+            diff.into_owned().apply(&mut m);
+            m
+        });
+
+        assert_eq!(map.insert("a", 1), None);
+        assert_eq!(map.insert("b", 2), None);
+        assert_eq!(map.insert("a", 3), Some(1));
+        assert_eq!(map.remove(&"b"), Some(2));
+        assert_eq!(map.remove(&"b"), None);
+        assert_eq!(view.get(&"a"), Some(3));
+        assert!(!view.contains_key(&"b"));
+        assert_eq!(synced.sample(), view.to_map());
+
+        map.replace(vec![("x", 10), ("y", 20)].into_iter().collect());
+        assert_eq!(view.len(), 2);
+        map.clear();
+        assert!(view.is_empty());
+        assert_eq!(synced.sample(), view.to_map());
+
+        assert_eq!(
+            diffs.sample(),
+            [
+                MapDiff::Insert { key: "a", value: 1 },
+                MapDiff::Insert { key: "b", value: 2 },
+                MapDiff::Update { key: "a", value: 3 },
+                MapDiff::Remove { key: "b" },
+                MapDiff::Replace {
+                    entries: vec![("x", 10), ("y", 20)]
+                },
+                MapDiff::Clear,
+            ]
+        );
+    }
+
+    #[test]
+    fn map_values_filter() {
+        let map: MutableMap<_, _> = MutableMap::from(
+            vec![(1, 10), (2, 20)]
+                .into_iter()
+                .collect::<BTreeMap<_, _>>(),
+        );
+        let labels = map
+            .signal_map()
+            .map_values(|k, v| alloc::format!("{}={}", k, v));
+        let big = map.signal_map().filter(|_, v| *v >= 20);
+        let diffs = big.diffs().collect::<Vec<_>>();
+        assert_eq!(big.to_map().into_iter().collect::<Vec<_>>(), [(2, 20)]);
+
+        map.insert(3, 30);
+        map.insert(1, 50);
+        map.insert(2, 5);
+        map.insert(4, 1);
+        map.remove(&3);
+        map.remove(&4);
+        assert_eq!(
+            labels.with(|m| m.values().cloned().collect::<Vec<String>>()),
+            ["1=50", "2=5"]
+        );
+        assert_eq!(
+            big.signal().sample().into_iter().collect::<Vec<_>>(),
+            [(1, 50)]
+        );
+
+        assert_eq!(
+            diffs.sample(),
+            [
+                MapDiff::Insert { key: 3, value: 30 },
+                MapDiff::Insert { key: 1, value: 50 },
+                MapDiff::Remove { key: 2 },
+                MapDiff::Remove { key: 3 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,663 @@
+//! Vector signal.
+
+use crate::collection::Shared;
+use crate::signal::Signal;
+use crate::stream::Stream;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+
+/// A change made to a vector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VecDiff<T> {
+    /// All the values were replaced.
+    Replace {
+        /// The new values.
+        values: Vec<T>,
+    },
+    /// A value was inserted.
+    Insert {
+        /// Position of the new value.
+        index: usize,
+        /// The value inserted.
+        value: T,
+    },
+    /// A value was removed.
+    Remove {
+        /// Position of the removed value.
+        index: usize,
+    },
+    /// A value was replaced.
+    Update {
+        /// Position of the value.
+        index: usize,
+        /// The new value.
+        value: T,
+    },
+    /// A value was moved.
+    ///
+    /// This is the same as removing the value at `old_index` and then inserting it at `new_index`.
+    Move {
+        /// Position of the value before the move.
+        old_index: usize,
+        /// Position of the value after the move.
+        new_index: usize,
+    },
+    /// All the values were removed.
+    Clear,
+}
+
+impl<T> VecDiff<T> {
+    /// Applies this change to a vector.
+    pub fn apply(self, vec: &mut Vec<T>) {
+        match self {
+            VecDiff::Replace { values } => *vec = values,
+            VecDiff::Insert { index, value } => vec.insert(index, value),
+            VecDiff::Remove { index } => {
+                vec.remove(index);
+            }
+            VecDiff::Update { index, value } => vec[index] = value,
+            VecDiff::Move {
+                old_index,
+                new_index,
+            } => {
+                let value = vec.remove(old_index);
+                vec.insert(new_index, value);
+            }
+            VecDiff::Clear => vec.clear(),
+        }
+    }
+}
+
+/// Applies a diff to a derived vector and sends it.
+fn emit<T: Clone>(values: &mut Vec<T>, out: &mut Vec<VecDiff<T>>, diff: VecDiff<T>) {
+    diff.clone().apply(values);
+    out.push(diff);
+}
+
+type SharedVec<T> = Shared<Vec<T>, VecDiff<T>>;
+
+/// A vector that sends a diff event on every change.
+///
+/// Clones of this object share the same vector. Use `MutableVec::signal_vec` to get a read-only
+/// view of it.
+pub struct MutableVec<T> {
+    shared: Arc<SharedVec<T>>,
+}
+
+impl<T: Clone + Send + Sync + 'static> MutableVec<T> {
+    /// Creates an empty vector.
+    #[inline]
+    pub fn new() -> Self {
+        Self::from(Vec::new())
+    }
+
+    /// Gets a read-only view of this vector.
+    #[inline]
+    pub fn signal_vec(&self) -> SignalVec<T> {
+        SignalVec {
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Appends a value to the end of the vector.
+    pub fn push(&self, value: T) {
+        self.shared.mutate(|vec, out| {
+            let index = vec.len();
+            emit(vec, out, VecDiff::Insert { index, value })
+        })
+    }
+
+    /// Inserts a value at position `index`.
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&self, index: usize, value: T) {
+        self.shared
+            .mutate(|vec, out| emit(vec, out, VecDiff::Insert { index, value }))
+    }
+
+    /// Removes the value at position `index` and returns it.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&self, index: usize) -> T {
+        self.shared.mutate(|vec, out| {
+            out.push(VecDiff::Remove { index });
+            vec.remove(index)
+        })
+    }
+
+    /// Removes the last value and returns it, or `None` if the vector is empty.
+    pub fn pop(&self) -> Option<T> {
+        self.shared.mutate(|vec, out| {
+            let value = vec.pop()?;
+            out.push(VecDiff::Remove { index: vec.len() });
+            Some(value)
+        })
+    }
+
+    /// Replaces the value at position `index`.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn set(&self, index: usize, value: T) {
+        self.shared
+            .mutate(|vec, out| emit(vec, out, VecDiff::Update { index, value }))
+    }
+
+    /// Moves the value at position `old_index` to `new_index`.
+    ///
+    /// Panics if any of the indices is out of bounds.
+    pub fn move_item(&self, old_index: usize, new_index: usize) {
+        self.shared.mutate(|vec, out| {
+            let diff = VecDiff::Move {
+                old_index,
+                new_index,
+            };
+            emit(vec, out, diff)
+        })
+    }
+
+    /// Removes all the values.
+    pub fn clear(&self) {
+        self.shared
+            .mutate(|vec, out| emit(vec, out, VecDiff::Clear))
+    }
+
+    /// Replaces all the values.
+    pub fn replace(&self, values: Vec<T>) {
+        self.shared
+            .mutate(|vec, out| emit(vec, out, VecDiff::Replace { values }))
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> From<Vec<T>> for MutableVec<T> {
+    /// Creates a vector with initial values.
+    #[inline]
+    fn from(values: Vec<T>) -> Self {
+        MutableVec {
+            shared: Shared::new(values, None),
+        }
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> Default for MutableVec<T> {
+    /// Creates an empty vector.
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for MutableVec<T> {
+    /// Creates a copy of this object that shares the same vector.
+    #[inline]
+    fn clone(&self) -> Self {
+        MutableVec {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MutableVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MutableVec").field(&self.shared).finish()
+    }
+}
+
+/// A read-only vector that sends a diff event on every change.
+pub struct SignalVec<T> {
+    shared: Arc<SharedVec<T>>,
+}
+
+impl<T: Clone + Send + Sync + 'static> SignalVec<T> {
+    /// Creates a vector that collects the values sent over a stream.
+    pub(crate) fn collect(stream: &Stream<T>) -> Self {
+        let shared = Shared::new(Vec::new(), Some(Arc::new(stream.clone())));
+        let weak = Arc::downgrade(&shared);
+        stream.observe(move |value| {
+            let value = value.into_owned();
+            with_weak!(weak, |shared| shared.mutate(|vec, out| {
+                let index = vec.len();
+                emit(vec, out, VecDiff::Insert { index, value })
+            }))
+        });
+        SignalVec { shared }
+    }
+
+    /// Gets the amount of values.
+    pub fn len(&self) -> usize {
+        self.shared.read(Vec::len)
+    }
+
+    /// Checks if the vector is empty.
+    pub fn is_empty(&self) -> bool {
+        self.shared.read(Vec::is_empty)
+    }
+
+    /// Gets a copy of the value at position `index`.
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.shared.read(|vec| vec.get(index).cloned())
+    }
+
+    /// Gets a copy of all the values.
+    pub fn to_vec(&self) -> Vec<T> {
+        self.shared.read(Vec::clone)
+    }
+
+    /// Reads the values without copying them.
+    ///
+    /// The vector is locked while the closure runs, so it must not modify it.
+    pub fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&[T]) -> R,
+    {
+        self.shared.read(|vec| f(vec))
+    }
+
+    /// Gets a stream of the changes made to the vector.
+    #[inline]
+    pub fn diffs(&self) -> Stream<VecDiff<T>> {
+        self.shared.diffs()
+    }
+
+    /// Gets a signal that samples a copy of the vector.
+    pub fn signal(&self) -> Signal<Vec<T>> {
+        let shared = self.shared.clone();
+        Signal::from_fn(move || shared.read(Vec::clone))
+    }
+
+    /// Creates a vector with the values transformed by a function.
+    pub fn map<F, R>(&self, f: F) -> SignalVec<R>
+    where
+        F: Fn(&T) -> R + Send + Sync + 'static,
+        R: Clone + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        let f_init = f.clone();
+        let shared = self.shared.derive(
+            move |vec| (vec.iter().map(&*f_init).collect(), ()),
+            move |_, diff, vec, out| {
+                let diff = match diff {
+                    VecDiff::Replace { values } => VecDiff::Replace {
+                        values: values.iter().map(&*f).collect(),
+                    },
+                    VecDiff::Insert { index, value } => VecDiff::Insert {
+                        index: *index,
+                        value: f(value),
+                    },
+                    VecDiff::Remove { index } => VecDiff::Remove { index: *index },
+                    VecDiff::Update { index, value } => VecDiff::Update {
+                        index: *index,
+                        value: f(value),
+                    },
+                    VecDiff::Move {
+                        old_index,
+                        new_index,
+                    } => VecDiff::Move {
+                        old_index: *old_index,
+                        new_index: *new_index,
+                    },
+                    VecDiff::Clear => VecDiff::Clear,
+                };
+                emit(vec, out, diff)
+            },
+        );
+        SignalVec { shared }
+    }
+
+    /// Creates a vector with only the values where the predicate is `true`.
+    pub fn filter<F>(&self, pred: F) -> SignalVec<T>
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        // position on the filtered vector of the value at `index` on the source vector
+        fn filtered_index(passes: &[bool], index: usize) -> usize {
+            passes[..index].iter().filter(|&&p| p).count()
+        }
+
+        let pred = Arc::new(pred);
+        let pred_init = pred.clone();
+        let shared = self.shared.derive(
+            move |vec| {
+                let passes: Vec<_> = vec.iter().map(&*pred_init).collect();
+                let values = vec
+                    .iter()
+                    .zip(&passes)
+                    .filter(|(_, &p)| p)
+                    .map(|(v, _)| v.clone())
+                    .collect();
+                (values, passes)
+            },
+            move |passes: &mut Vec<bool>, diff, vec, out| match diff {
+                VecDiff::Replace { values } => {
+                    *passes = values.iter().map(&*pred).collect();
+                    let values = values.iter().filter(|v| pred(v)).cloned().collect();
+                    emit(vec, out, VecDiff::Replace { values })
+                }
+                VecDiff::Insert { index, value } => {
+                    let p = pred(value);
+                    passes.insert(*index, p);
+                    if p {
+                        let index = filtered_index(passes, *index);
+                        let value = value.clone();
+                        emit(vec, out, VecDiff::Insert { index, value })
+                    }
+                }
+                VecDiff::Remove { index } => {
+                    if passes.remove(*index) {
+                        let index = filtered_index(passes, *index);
+                        emit(vec, out, VecDiff::Remove { index })
+                    }
+                }
+                VecDiff::Update { index, value } => {
+                    let (old, new) = (passes[*index], pred(value));
+                    passes[*index] = new;
+                    let index = filtered_index(passes, *index);
+                    let value = value.clone();
+                    match (old, new) {
+                        (true, true) => emit(vec, out, VecDiff::Update { index, value }),
+                        (true, false) => emit(vec, out, VecDiff::Remove { index }),
+                        (false, true) => emit(vec, out, VecDiff::Insert { index, value }),
+                        (false, false) => (),
+                    }
+                }
+                VecDiff::Move {
+                    old_index,
+                    new_index,
+                } => {
+                    let old_pos = filtered_index(passes, *old_index);
+                    let p = passes.remove(*old_index);
+                    passes.insert(*new_index, p);
+                    let new_pos = filtered_index(passes, *new_index);
+                    if p && old_pos != new_pos {
+                        let diff = VecDiff::Move {
+                            old_index: old_pos,
+                            new_index: new_pos,
+                        };
+                        emit(vec, out, diff)
+                    }
+                }
+                VecDiff::Clear => {
+                    passes.clear();
+                    emit(vec, out, VecDiff::Clear)
+                }
+            },
+        );
+        SignalVec { shared }
+    }
+
+    /// Creates a vector with the values sorted by a comparison function.
+    ///
+    /// Values that compare equal keep the order in which they were added.
+    pub fn sort_by<F>(&self, cmp: F) -> SignalVec<T>
+    where
+        F: Fn(&T, &T) -> Ordering + Send + Sync + 'static,
+    {
+        // the source indices sorted by value
+        fn sorted_indices<T>(values: &[T], cmp: impl Fn(&T, &T) -> Ordering) -> Vec<usize> {
+            let mut order: Vec<_> = (0..values.len()).collect();
+            order.sort_by(|&a, &b| cmp(&values[a], &values[b]));
+            order
+        }
+
+        let cmp = Arc::new(cmp);
+        let cmp_init = cmp.clone();
+        let shared = self.shared.derive(
+            move |vec| {
+                let order = sorted_indices(vec, &*cmp_init);
+                let values = order.iter().map(|&i| vec[i].clone()).collect();
+                (values, order)
+            },
+            move |order: &mut Vec<usize>, diff, vec, out| {
+                // position where a value must be inserted, after all the equal ones
+                let sorted_pos = |vec: &[T], value: &T| {
+                    vec.partition_point(|v| cmp(v, value) != Ordering::Greater)
+                };
+                let find =
+                    |order: &[usize], index: usize| order.iter().position(|&i| i == index).unwrap();
+                match diff {
+                    VecDiff::Replace { values } => {
+                        *order = sorted_indices(values, &*cmp);
+                        let values = order.iter().map(|&i| values[i].clone()).collect();
+                        emit(vec, out, VecDiff::Replace { values })
+                    }
+                    VecDiff::Insert { index, value } => {
+                        for i in order.iter_mut().filter(|i| **i >= *index) {
+                            *i += 1;
+                        }
+                        let pos = sorted_pos(vec, value);
+                        order.insert(pos, *index);
+                        let value = value.clone();
+                        emit(vec, out, VecDiff::Insert { index: pos, value })
+                    }
+                    VecDiff::Remove { index } => {
+                        let pos = find(order, *index);
+                        order.remove(pos);
+                        for i in order.iter_mut().filter(|i| **i > *index) {
+                            *i -= 1;
+                        }
+                        emit(vec, out, VecDiff::Remove { index: pos })
+                    }
+                    VecDiff::Update { index, value } => {
+                        let old_pos = find(order, *index);
+                        order.remove(old_pos);
+                        vec.remove(old_pos);
+                        let new_pos = sorted_pos(vec, value);
+                        order.insert(new_pos, *index);
+                        vec.insert(new_pos, value.clone());
+                        if old_pos != new_pos {
+                            out.push(VecDiff::Move {
+                                old_index: old_pos,
+                                new_index: new_pos,
+                            });
+                        }
+                        out.push(VecDiff::Update {
+                            index: new_pos,
+                            value: value.clone(),
+                        });
+                    }
+                    VecDiff::Move {
+                        old_index,
+                        new_index,
+                    } => {
+                        // the sorted order doesn't change, only the source indices
+                        for i in order.iter_mut() {
+                            *i = if *i == *old_index {
+                                *new_index
+                            } else {
+                                let i = if *i > *old_index { *i - 1 } else { *i };
+                                if i >= *new_index {
+                                    i + 1
+                                } else {
+                                    i
+                                }
+                            };
+                        }
+                    }
+                    VecDiff::Clear => {
+                        order.clear();
+                        emit(vec, out, VecDiff::Clear)
+                    }
+                }
+            },
+        );
+        SignalVec { shared }
+    }
+}
+
+impl<T> Clone for SignalVec<T> {
+    /// Creates a copy of this object that shares the same vector.
+    #[inline]
+    fn clone(&self) -> Self {
+        SignalVec {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SignalVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SignalVec").field(&self.shared).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sink;
+    use alloc::vec;
+
+    /// Checks that a view matches a vector rebuilt by applying it's diffs.
+    fn check_diffs<T>(view: &SignalVec<T>) -> impl Fn() + '_
+    where
+        T: Clone + PartialEq + fmt::Debug + Send + Sync + 'static,
+    {
+        let synced = view.diffs().fold(view.to_vec(), |mut v, diff| {
+            diff.into_owned().apply(&mut v);
+            v
+        });
+        move || assert_eq!(synced.sample(), view.to_vec())
+    }
+
+    #[test]
+    fn vec_mutate() {
+        let vec = MutableVec::from(vec![1, 2, 3]);
+        let view = vec.signal_vec();
+        let diffs = view.diffs().collect::<Vec<_>>();
+        let check = check_diffs(&view);
+
+        vec.push(4);
+        vec.insert(0, 0);
+        assert_eq!(vec.remove(2), 2);
+        vec.set(1, 10);
+        vec.move_item(0, 3);
+        assert_eq!(vec.pop(), Some(0));
+        assert_eq!(view.to_vec(), [10, 3, 4]);
+        check();
+        vec.replace(vec![5, 6]);
+        vec.clear();
+        assert!(view.is_empty());
+        check();
+
+        assert_eq!(
+            diffs.sample(),
+            [
+                VecDiff::Insert { index: 3, value: 4 },
+                VecDiff::Insert { index: 0, value: 0 },
+                VecDiff::Remove { index: 2 },
+                VecDiff::Update {
+                    index: 1,
+                    value: 10
+                },
+                VecDiff::Move {
+                    old_index: 0,
+                    new_index: 3
+                },
+                VecDiff::Remove { index: 3 },
+                VecDiff::Replace { values: vec![5, 6] },
+                VecDiff::Clear,
+            ]
+        );
+    }
+
+    #[test]
+    fn vec_map_filter() {
+        let vec = MutableVec::from(vec![1, 2, 3, 4]);
+        let doubled = vec.signal_vec().map(|x| x * 2);
+        let even = vec.signal_vec().filter(|x| x % 2 == 0);
+        let diffs = even.diffs().collect::<Vec<_>>();
+        let (check1, check2) = (check_diffs(&doubled), check_diffs(&even));
+        assert_eq!(doubled.to_vec(), [2, 4, 6, 8]);
+        assert_eq!(even.to_vec(), [2, 4]);
+
+        vec.push(6);
+        vec.insert(0, 7);
+        vec.set(1, 8);
+        vec.set(2, 9);
+        vec.move_item(4, 0);
+        vec.remove(3);
+        assert_eq!(vec.signal_vec().to_vec(), [4, 7, 8, 3, 6]);
+        assert_eq!(doubled.to_vec(), [8, 14, 16, 6, 12]);
+        assert_eq!(even.to_vec(), [4, 8, 6]);
+        check1();
+        check2();
+
+        assert_eq!(
+            diffs.sample(),
+            [
+                VecDiff::Insert { index: 2, value: 6 },
+                VecDiff::Insert { index: 0, value: 8 },
+                VecDiff::Remove { index: 1 },
+                VecDiff::Move {
+                    old_index: 1,
+                    new_index: 0
+                },
+            ]
+        );
+
+        vec.replace(vec![10, 11, 12]);
+        assert_eq!(even.to_vec(), [10, 12]);
+        vec.clear();
+        assert!(doubled.is_empty());
+        check1();
+        check2();
+    }
+
+    #[test]
+    fn vec_sort_by() {
+        let vec = MutableVec::from(vec![3, 1, 2]);
+        let sorted = vec.signal_vec().sort_by(|a, b| a.cmp(b));
+        let check = check_diffs(&sorted);
+        assert_eq!(sorted.to_vec(), [1, 2, 3]);
+
+        vec.push(0);
+        vec.insert(1, 5);
+        vec.set(0, 4);
+        vec.move_item(0, 4);
+        vec.remove(0);
+        vec.set(0, 1);
+        assert_eq!(vec.signal_vec().to_vec(), [1, 2, 0, 4]);
+        assert_eq!(sorted.to_vec(), [0, 1, 2, 4]);
+        check();
+
+        // the chained views are kept alive by the last one
+        let top = vec.signal_vec().map(|x: &i32| -x).sort_by(|a, b| a.cmp(b));
+        vec.push(9);
+        assert_eq!(top.get(0), Some(-9));
+    }
+
+    #[test]
+    fn vec_collect() {
+        let sink = Sink::new();
+        let vec = sink.stream().filter(|x| x % 2 != 0).collect_vec();
+        let even = vec.filter(|x| *x > 2);
+        sink.feed(0..6);
+        assert_eq!(vec.to_vec(), [1, 3, 5]);
+        assert_eq!(even.to_vec(), [3, 5]);
+        assert_eq!(vec.with(|v| v.iter().sum::<i32>()), 9);
+        assert_eq!(vec.signal().sample(), [1, 3, 5]);
+    }
+
+    #[test]
+    fn vec_reentrant() {
+        let vec = MutableVec::new();
+        let vec_ = vec.clone();
+        // removes every odd value as soon as it's added
+        let diffs = vec.signal_vec().diffs();
+        diffs.observe(move |diff| {
+            if let VecDiff::Insert { index, value } = *diff {
+                if value % 2 != 0 {
+                    vec_.remove(index);
+                }
+            }
+        });
+        let mirror = vec.signal_vec().map(|x| *x);
+        let check = check_diffs(&mirror);
+        for i in 0..5 {
+            vec.push(i);
+        }
+        assert_eq!(vec.signal_vec().to_vec(), [0, 2, 4]);
+        assert_eq!(mirror.to_vec(), [0, 2, 4]);
+        check();
+    }
+}
@@ -0,0 +1,382 @@
+//! Introspection of the event graph.
+//!
+//! Every `Sink`, stream operation and signal storage created from a stream is a node of the event
+//! graph. The `graph` function takes a snapshot of all the nodes currently alive, that can be
+//! exported in Graphviz DOT or JSON format to find out why an event doesn't reach it's destination.
+//! Keeping track of the nodes has a cost on every node creation, so it must be enabled with
+//! `set_graph_tracking`, and only the nodes created after that are included on the snapshot.
+//!
+//! Streams can be given a name with `Stream::named` to make them easier to find.
+//!
+//! Each node also keeps event counters (see `Stream::stats`), that are included on the snapshot.
+//! Measuring the time spent on the callbacks has a higher cost, so it must be enabled with
+//! `set_latency_tracking`, or with `Stream::track_latency` for a single stream.
+//! Streams from the `local` module aren't tracked.
+//!
+//! # Example
+//! ```
+//! use frappe::{debug, Sink};
+//!
+//! debug::set_graph_tracking(true);
+//! let sink = Sink::<i32>::new();
+//! let clicks = sink.stream().named("clicks");
+//! let count = clicks.fold(0, |n, _| n + 1);
+//!
+//! let graph = debug::graph();
+//! let node = graph.find("clicks").unwrap();
+//! assert_eq!(node.observers, 1);
+//! println!("{}", graph.to_dot());
+//! ```
+
+use crate::types::NodeInfo;
+pub use crate::types::{LatencyHistogram, NodeKind, NodeStats};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+/// All the nodes created, alive or not.
+static REGISTRY: Mutex<Vec<Weak<NodeInfo>>> = Mutex::new(Vec::new());
+
+/// Enables the node registry.
+static GRAPH_TRACKING: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables adding the new nodes to the graph returned by `graph`.
+///
+/// The nodes created while it's disabled are never added.
+pub fn set_graph_tracking(enabled: bool) {
+    GRAPH_TRACKING.store(enabled, Ordering::Relaxed);
+}
+
+/// Checks if the new nodes are being added to the graph.
+#[inline]
+pub fn graph_tracking() -> bool {
+    GRAPH_TRACKING.load(Ordering::Relaxed)
+}
+
+/// Enables the latency histograms.
+static LATENCY_TRACKING: AtomicBool = AtomicBool::new(false);
+
+/// Enables or disables measuring the time spent on the stream callbacks.
+///
+/// This affects all the nodes, use `Stream::track_latency` to measure a single stream. The
+/// measures are added to the `NodeStats::latency` histogram.
+pub fn set_latency_tracking(enabled: bool) {
+    LATENCY_TRACKING.store(enabled, Ordering::Relaxed);
+}
+
+/// Checks if the time spent on the stream callbacks is being measured.
+#[inline]
+pub fn latency_tracking() -> bool {
+    LATENCY_TRACKING.load(Ordering::Relaxed)
+}
+
+/// Adds a node to the registry, if graph tracking is enabled.
+pub(crate) fn register(node: &Arc<NodeInfo>) {
+    if !graph_tracking() {
+        return;
+    }
+    let mut nodes = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    // remove the dead nodes before growing the list
+    if nodes.len() == nodes.capacity() {
+        nodes.retain(|n| n.strong_count() > 0);
+    }
+    nodes.push(Arc::downgrade(node));
+}
+
+/// A snapshot of a node of the event graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    /// Unique id of the node.
+    pub id: usize,
+    /// The role of the node.
+    pub kind: NodeKind,
+    /// The operation that created the node.
+    pub operator: &'static str,
+    /// The name given with `Stream::named`.
+    pub name: Option<String>,
+    /// Type name of the values that pass through the node.
+    pub value_type: &'static str,
+    /// Ids of the nodes that send values to this node.
+    pub parents: Vec<usize>,
+    /// Amount of callbacks registered on the node.
+    pub observers: usize,
+    /// The event counters of the node.
+    pub stats: NodeStats,
+}
+
+impl Node {
+    fn from_info(info: &NodeInfo) -> Self {
+        Node {
+            id: info.id(),
+            kind: info.kind(),
+            operator: info.operator(),
+            name: info.name().map(|s| s.to_string()),
+            value_type: info.value_type(),
+            parents: info.parents().to_vec(),
+            observers: info.observers(),
+            stats: info.stats(),
+        }
+    }
+
+    /// A short description of the node.
+    fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{} \"{}\"", self.operator, name),
+            None => self.operator.to_string(),
+        }
+    }
+}
+
+/// A snapshot of the event graph.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Graph {
+    /// The nodes alive when the snapshot was taken, sorted by id.
+    pub nodes: Vec<Node>,
+}
+
+impl Graph {
+    /// Finds a node by name.
+    pub fn find(&self, name: &str) -> Option<&Node> {
+        self.nodes.iter().find(|n| n.name.as_deref() == Some(name))
+    }
+
+    /// Gets a node by id.
+    pub fn get(&self, id: usize) -> Option<&Node> {
+        self.nodes
+            .binary_search_by_key(&id, |n| n.id)
+            .ok()
+            .map(|i| &self.nodes[i])
+    }
+
+    /// Exports the graph in Graphviz DOT format.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph frappe {\n");
+        for node in &self.nodes {
+            let shape = match node.kind {
+                NodeKind::Sink => "house",
+                NodeKind::Stream => "ellipse",
+                NodeKind::Storage => "box",
+            };
+            let _ = writeln!(
+                out,
+                "    n{} [shape={}, label=\"{}\\n{}\\nobservers: {}\\nevents: {}\"];",
+                node.id,
+                shape,
+                escape_dot(&node.label()),
+                escape_dot(node.value_type),
+                node.observers,
+                node.stats.events_in
+            );
+        }
+        for node in &self.nodes {
+            for parent in &node.parents {
+                let _ = writeln!(out, "    n{} -> n{};", parent, node.id);
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Exports the graph in JSON format.
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\"nodes\":[");
+        for (i, node) in self.nodes.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let kind = match node.kind {
+                NodeKind::Sink => "sink",
+                NodeKind::Stream => "stream",
+                NodeKind::Storage => "storage",
+            };
+            let _ = write!(
+                out,
+                "{{\"id\":{},\"kind\":\"{}\",\"operator\":\"{}\",\"name\":",
+                node.id,
+                kind,
+                escape_json(node.operator)
+            );
+            match &node.name {
+                Some(name) => {
+                    let _ = write!(out, "\"{}\"", escape_json(name));
+                }
+                None => out.push_str("null"),
+            }
+            let parents: Vec<_> = node.parents.iter().map(|p| p.to_string()).collect();
+            let _ = write!(
+                out,
+                ",\"value_type\":\"{}\",\"parents\":[{}],\"observers\":{}",
+                escape_json(node.value_type),
+                parents.join(","),
+                node.observers
+            );
+            let stats = &node.stats;
+            let _ = write!(
+                out,
+                ",\"events_in\":{},\"events_out\":{},\"dropped\":{},\"latency\":",
+                stats.events_in, stats.events_out, stats.dropped
+            );
+            match &stats.latency {
+                Some(latency) => {
+                    let buckets: Vec<_> = latency.buckets.iter().map(|n| n.to_string()).collect();
+                    let _ = write!(
+                        out,
+                        "{{\"buckets\":[{}],\"total_ns\":{}}}}}",
+                        buckets.join(","),
+                        latency.total.as_nanos()
+                    );
+                }
+                None => out.push_str("null}"),
+            }
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+/// Takes a snapshot of all the nodes currently alive.
+///
+/// Only the nodes created while graph tracking was enabled are included.
+pub fn graph() -> Graph {
+    let alive: Vec<_> = {
+        let mut nodes = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+        nodes.retain(|n| n.strong_count() > 0);
+        nodes.iter().filter_map(Weak::upgrade).collect()
+    };
+    let mut nodes: Vec<_> = alive.iter().map(|n| Node::from_info(n)).collect();
+    nodes.sort_by_key(|n| n.id);
+    Graph { nodes }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_json(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sink;
+
+    #[test]
+    fn graph_nodes() {
+        set_graph_tracking(true);
+        let sink = Sink::<i32>::new();
+        let input = sink.stream().named("graph_nodes/input");
+        let doubled = input.map(|x| *x * 2).named("graph_nodes/doubled");
+        let odd = input.filter(|x| x % 2 != 0);
+        let merged = doubled.merge(&odd).named("graph_nodes/merged");
+        let _sum = merged.fold(0, |a, n| a + *n);
+
+        let graph = graph();
+        let input = graph.find("graph_nodes/input").unwrap();
+        let doubled = graph.find("graph_nodes/doubled").unwrap();
+        let merged = graph.find("graph_nodes/merged").unwrap();
+        let odd = graph.get(merged.parents[1]).unwrap();
+        let sum = graph
+            .nodes
+            .iter()
+            .find(|n| n.parents == [merged.id])
+            .unwrap();
+
+        assert_eq!(input.kind, NodeKind::Sink);
+        assert_eq!(input.observers, 2);
+        assert_eq!(input.value_type, "i32");
+        assert_eq!(doubled.operator, "map");
+        assert_eq!(doubled.parents, [input.id]);
+        assert_eq!(odd.operator, "filter");
+        assert_eq!(odd.parents, [input.id]);
+        assert_eq!(merged.operator, "merge");
+        assert_eq!(merged.parents, [doubled.id, odd.id]);
+        assert_eq!(merged.observers, 1);
+        assert_eq!(sum.kind, NodeKind::Storage);
+        assert_eq!(sum.operator, "fold");
+    }
+
+    #[test]
+    fn graph_dead_nodes() {
+        set_graph_tracking(true);
+        let sink = Sink::<i32>::new();
+        let mapped = sink.stream().map(|x| *x).named("graph_dead_nodes");
+        assert!(graph().find("graph_dead_nodes").is_some());
+
+        drop(mapped);
+        assert!(graph().find("graph_dead_nodes").is_none());
+    }
+
+    #[test]
+    fn graph_export() {
+        set_graph_tracking(true);
+        let sink = Sink::<String>::new();
+        let input = sink.stream().named("graph_export \"in\"");
+        let _len = input.map(|s| s.len()).hold(0);
+
+        let graph = graph();
+        let input = graph.find("graph_export \"in\"").unwrap();
+        let len = graph
+            .nodes
+            .iter()
+            .find(|n| n.parents == [input.id])
+            .unwrap();
+        let hold = graph.nodes.iter().find(|n| n.parents == [len.id]).unwrap();
+        let dot = graph.to_dot();
+        let json = graph.to_json();
+
+        assert!(dot.starts_with("digraph frappe {\n"));
+        assert!(dot.contains(&format!(
+            "n{} [shape=house, label=\"sink \\\"graph_export \\\"in\\\"\\\"\\nalloc::string::String\\nobservers: 1\\nevents: 0\"];",
+            input.id
+        )));
+        assert!(dot.contains(&format!("n{} -> n{};", input.id, len.id)));
+        assert!(dot.contains(&format!("n{} -> n{};", len.id, hold.id)));
+        assert!(json.contains(&format!(
+            "{{\"id\":{},\"kind\":\"sink\",\"operator\":\"sink\",\"name\":\"graph_export \\\"in\\\"\",\"value_type\":\"alloc::string::String\",\"parents\":[],\"observers\":1,\"events_in\":0,\"events_out\":0,\"dropped\":0,\"latency\":null}}",
+            input.id
+        )));
+        assert!(json.contains(&format!(
+            "{{\"id\":{},\"kind\":\"storage\",\"operator\":\"hold\",\"name\":null,\"value_type\":\"usize\",\"parents\":[{}],\"observers\":0,\"events_in\":0,\"events_out\":0,\"dropped\":0,\"latency\":null}}",
+            hold.id, len.id
+        )));
+    }
+
+    #[test]
+    fn graph_stats() {
+        set_graph_tracking(true);
+        let sink = Sink::<i32>::new();
+        let input = sink.stream().named("graph_stats/input").track_latency(true);
+        let even = input.filter(|x| x % 2 == 0).named("graph_stats/even");
+        let _sum = even.fold(0, |a, n| a + *n);
+        // dies after receiving 4
+        even.observe(|x| *x < 4);
+
+        sink.feed(0..10);
+
+        let graph = graph();
+        let input = &graph.find("graph_stats/input").unwrap().stats;
+        let even = &graph.find("graph_stats/even").unwrap().stats;
+        assert_eq!(input.events_in, 10);
+        assert_eq!(input.events_out, 10);
+        assert_eq!(input.latency.as_ref().unwrap().count(), 10);
+        assert_eq!(even.events_in, 5);
+        assert_eq!(even.events_out, 5 + 3);
+        assert_eq!(even.dropped, 1);
+        assert!(graph.to_json().contains("\"latency\":{\"buckets\":["));
+    }
+}
@@ -0,0 +1,542 @@
+//! Finite state machines driven by streams.
+//!
+//! A state machine is declared with a `Builder`: first the states, and then the transitions
+//! between them. Each transition is triggered by the events of a stream, and can have a guard
+//! signal that must be `true` for the transition to happen. The resulting `Machine` provides the
+//! current state as a signal, and streams with the transitions made.
+//!
+//! When a trigger stream sends an event that has no valid transition from the current state, the
+//! state doesn't change and the event is reported on the `Machine::invalid` stream.
+//!
+//! # Example
+//! ```
+//! use frappe::fsm::{Builder, InvalidTransition};
+//! use frappe::Sink;
+//!
+//! #[derive(Debug, Clone, PartialEq)]
+//! enum Player {
+//!     Stopped,
+//!     Playing,
+//!     Paused,
+//! }
+//!
+//! let play = Sink::<()>::new();
+//! let pause = Sink::<()>::new();
+//! let stop = Sink::<()>::new();
+//! let player = Builder::new(Player::Stopped)
+//!     .state(Player::Playing)
+//!     .state(Player::Paused)
+//!     .transition(Player::Stopped, Player::Playing, &play.stream())
+//!     .transition(Player::Paused, Player::Playing, &play.stream())
+//!     .transition(Player::Playing, Player::Paused, &pause.stream())
+//!     .transition(Player::Playing, Player::Stopped, &stop.stream())
+//!     .transition(Player::Paused, Player::Stopped, &stop.stream())
+//!     .build();
+//! let state = player.state();
+//! let errors = player.invalid().collect::<Vec<_>>();
+//!
+//! play.send(());
+//! pause.send(());
+//! assert_eq!(state.sample(), Player::Paused);
+//! pause.send(());
+//! assert_eq!(
+//!     errors.sample(),
+//!     [InvalidTransition::NoTransition { state: Player::Paused, trigger: 1 }]
+//! );
+//! ```
+
+use crate::signal::Signal;
+use crate::stream::{Sink, Stream};
+use crate::sync::Mutex;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt;
+
+/// A change of state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transition<S> {
+    /// The state before the transition.
+    pub from: S,
+    /// The state after the transition.
+    pub to: S,
+}
+
+/// An event that couldn't change the state.
+///
+/// The trigger streams are numbered in the order they were first used on the builder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidTransition<S> {
+    /// The trigger has no transitions from the current state.
+    NoTransition {
+        /// The current state.
+        state: S,
+        /// Number of the trigger stream.
+        trigger: usize,
+    },
+    /// All the transitions from the current state were blocked by their guards.
+    Blocked {
+        /// The current state.
+        state: S,
+        /// Number of the trigger stream.
+        trigger: usize,
+    },
+}
+
+/// A transition declared on the builder.
+struct Rule<S> {
+    trigger: usize,
+    from: S,
+    to: S,
+    guard: Option<Signal<bool>>,
+}
+
+/// Subscribes the machine to a trigger stream, and returns the stream to keep it alive.
+type Subscribe<S> = Box<dyn FnOnce(Weak<Inner<S>>, usize) -> Box<dyn Any + Send + Sync>>;
+
+/// A trigger stream used on the builder.
+struct Trigger<S> {
+    node: usize,
+    subscribe: Subscribe<S>,
+}
+
+/// Builds a state machine.
+pub struct Builder<S> {
+    initial: S,
+    states: Vec<S>,
+    rules: Vec<Rule<S>>,
+    triggers: Vec<Trigger<S>>,
+}
+
+impl<S: Clone + PartialEq + Send + Sync + 'static> Builder<S> {
+    /// Starts building a state machine with the initial state.
+    ///
+    /// The initial state is declared automatically.
+    pub fn new(initial: S) -> Self {
+        Builder {
+            states: alloc::vec![initial.clone()],
+            initial,
+            rules: Vec::new(),
+            triggers: Vec::new(),
+        }
+    }
+
+    /// Declares a state.
+    pub fn state(mut self, state: S) -> Self {
+        if !self.states.contains(&state) {
+            self.states.push(state);
+        }
+        self
+    }
+
+    /// Adds a transition that is made every time the trigger stream sends an event.
+    ///
+    /// When multiple transitions from the same state use the same trigger, the first one declared
+    /// whose guard is `true` is made.
+    pub fn transition<T: 'static>(self, from: S, to: S, trigger: &Stream<T>) -> Self {
+        self.add_rule(from, to, trigger, None)
+    }
+
+    /// Adds a transition that is made when the trigger stream sends an event and the guard is
+    /// `true`.
+    ///
+    /// The guard is sampled while the machine is locked, so it must not sample the state of the
+    /// same machine.
+    pub fn guarded_transition<T: 'static>(
+        self,
+        from: S,
+        to: S,
+        trigger: &Stream<T>,
+        guard: &Signal<bool>,
+    ) -> Self {
+        self.add_rule(from, to, trigger, Some(guard.clone()))
+    }
+
+    fn add_rule<T: 'static>(
+        mut self,
+        from: S,
+        to: S,
+        trigger: &Stream<T>,
+        guard: Option<Signal<bool>>,
+    ) -> Self {
+        let node = trigger.node_id();
+        let trigger = match self.triggers.iter().position(|t| t.node == node) {
+            Some(pos) => pos,
+            None => {
+                let stream = trigger.clone();
+                self.triggers.push(Trigger {
+                    node,
+                    subscribe: Box::new(move |weak, trigger| {
+                        stream.observe(move |_| with_weak!(weak, |m| m.fire(trigger)));
+                        Box::new(stream)
+                    }),
+                });
+                self.triggers.len() - 1
+            }
+        };
+        self.rules.push(Rule {
+            trigger,
+            from,
+            to,
+            guard,
+        });
+        self
+    }
+
+    /// Creates the state machine.
+    ///
+    /// Panics if a transition uses a state that wasn't declared.
+    pub fn build(self) -> Machine<S> {
+        for rule in &self.rules {
+            assert!(
+                self.states.contains(&rule.from) && self.states.contains(&rule.to),
+                "transition uses an undeclared state"
+            );
+        }
+        let inner = Arc::new(Inner {
+            state: Mutex::new(self.initial),
+            rules: self.rules,
+            outbox: Mutex::new(Outbox {
+                queue: VecDeque::new(),
+                draining: false,
+            }),
+            transitions: Sink::new(),
+            invalid: Sink::new(),
+        });
+        let triggers = self
+            .triggers
+            .into_iter()
+            .enumerate()
+            .map(|(i, t)| (t.subscribe)(Arc::downgrade(&inner), i))
+            .collect::<Vec<_>>();
+        Machine {
+            inner,
+            _triggers: triggers.into(),
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for Builder<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Builder")
+            .field("initial", &self.initial)
+            .field("states", &self.states)
+            .finish()
+    }
+}
+
+/// The state shared by the machine and it's triggers.
+struct Inner<S> {
+    state: Mutex<S>,
+    rules: Vec<Rule<S>>,
+    outbox: Mutex<Outbox<S>>,
+    transitions: Sink<Transition<S>>,
+    invalid: Sink<InvalidTransition<S>>,
+}
+
+/// The results waiting to be sent, in the order the state changed.
+struct Outbox<S> {
+    queue: VecDeque<Result<Transition<S>, InvalidTransition<S>>>,
+    draining: bool,
+}
+
+impl<S: Clone + PartialEq> Inner<S> {
+    /// Handles an event from a trigger stream.
+    fn fire(&self, trigger: usize) {
+        {
+            let mut state = self.state.lock();
+            let mut rules = self
+                .rules
+                .iter()
+                .filter(|r| r.trigger == trigger && r.from == *state)
+                .peekable();
+            let found = rules.peek().is_some();
+            let result = match rules.find(|r| r.guard.as_ref().is_none_or(Signal::sample)) {
+                Some(rule) => {
+                    let from = core::mem::replace(&mut *state, rule.to.clone());
+                    Ok(Transition {
+                        from,
+                        to: rule.to.clone(),
+                    })
+                }
+                None if found => Err(InvalidTransition::Blocked {
+                    state: state.clone(),
+                    trigger,
+                }),
+                None => Err(InvalidTransition::NoTransition {
+                    state: state.clone(),
+                    trigger,
+                }),
+            };
+            // queued before the state is unlocked, so the queue follows the state changes
+            self.outbox.lock().queue.push_back(result);
+        }
+        self.drain();
+    }
+
+    /// Sends the queued results.
+    ///
+    /// Only one caller sends at a time, the others return right away and leave their results to
+    /// it. This keeps the results in order even when multiple threads fire the machine, or when an
+    /// observer fires it again while it's sending.
+    fn drain(&self) {
+        {
+            let mut outbox = self.outbox.lock();
+            if outbox.draining {
+                return;
+            }
+            outbox.draining = true;
+        }
+        let mut drainer = Drainer {
+            outbox: &self.outbox,
+            done: false,
+        };
+        while let Some(result) = drainer.next() {
+            match result {
+                Ok(t) => self.transitions.send(t),
+                Err(e) => self.invalid.send(e),
+            }
+        }
+    }
+}
+
+/// Takes the results out of the outbox while it's marked as draining.
+struct Drainer<'a, S> {
+    outbox: &'a Mutex<Outbox<S>>,
+    done: bool,
+}
+
+impl<S> Drainer<'_, S> {
+    /// Takes the next result, or stops draining if there are none left.
+    ///
+    /// The check and the stop happen under the same lock, so no result can be left behind.
+    fn next(&mut self) -> Option<Result<Transition<S>, InvalidTransition<S>>> {
+        let mut outbox = self.outbox.lock();
+        let result = outbox.queue.pop_front();
+        if result.is_none() {
+            outbox.draining = false;
+            self.done = true;
+        }
+        result
+    }
+}
+
+impl<S> Drop for Drainer<'_, S> {
+    /// Lets another caller drain the outbox if an observer panicked.
+    fn drop(&mut self) {
+        if !self.done {
+            self.outbox.lock().draining = false;
+        }
+    }
+}
+
+/// A state machine driven by streams.
+///
+/// It's created with a `Builder`.
+pub struct Machine<S> {
+    inner: Arc<Inner<S>>,
+    _triggers: Arc<[Box<dyn Any + Send + Sync>]>,
+}
+
+impl<S: Clone + PartialEq + Send + Sync + 'static> Machine<S> {
+    /// Starts building a state machine with the initial state.
+    #[inline]
+    pub fn builder(initial: S) -> Builder<S> {
+        Builder::new(initial)
+    }
+
+    /// Gets a signal with the current state.
+    ///
+    /// The signal keeps the machine alive.
+    pub fn state(&self) -> Signal<S> {
+        let this = self.clone();
+        Signal::from_fn(move || this.inner.state.lock().clone())
+    }
+
+    /// Gets a stream of the transitions made.
+    ///
+    /// The transitions are sent in the order they're made, even when the triggers fire from
+    /// multiple threads. A transition triggered by an observer of this stream is sent after the
+    /// current one reached all the observers.
+    #[inline]
+    pub fn transitions(&self) -> Stream<Transition<S>> {
+        self.inner.transitions.stream()
+    }
+
+    /// Gets a stream that sends the previous state every time the machine enters `state`.
+    pub fn on_enter(&self, state: S) -> Stream<S> {
+        self.transitions()
+            .filter_map(move |t| (t.to == state).then(|| t.from.clone()))
+    }
+
+    /// Gets a stream that sends the next state every time the machine exits `state`.
+    pub fn on_exit(&self, state: S) -> Stream<S> {
+        self.transitions()
+            .filter_map(move |t| (t.from == state).then(|| t.to.clone()))
+    }
+
+    /// Gets a stream of the events that couldn't change the state.
+    #[inline]
+    pub fn invalid(&self) -> Stream<InvalidTransition<S>> {
+        self.inner.invalid.stream()
+    }
+}
+
+impl<S> Clone for Machine<S> {
+    /// Creates a copy of this object that shares the same state.
+    fn clone(&self) -> Self {
+        Machine {
+            inner: self.inner.clone(),
+            _triggers: self._triggers.clone(),
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for Machine<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Machine")
+            .field("state", &self.inner.state)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sink;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Door {
+        Closed,
+        Open,
+        Locked,
+    }
+
+    #[test]
+    fn fsm_transitions() {
+        let toggle = Sink::new();
+        let lock = Sink::<()>::new();
+        let has_key = Sink::new();
+        let key = has_key.stream().hold(false);
+        let door = Machine::builder(Door::Closed)
+            .state(Door::Open)
+            .state(Door::Locked)
+            .transition(Door::Closed, Door::Open, &toggle.stream())
+            .transition(Door::Open, Door::Closed, &toggle.stream())
+            .guarded_transition(Door::Closed, Door::Locked, &lock.stream(), &key)
+            .guarded_transition(Door::Locked, Door::Closed, &lock.stream(), &key)
+            .build();
+        let state = door.state();
+        let entered = door.on_enter(Door::Locked).collect::<Vec<_>>();
+        let exited = door.on_exit(Door::Closed).collect::<Vec<_>>();
+        let invalid = door.invalid().collect::<Vec<_>>();
+        drop(door);
+
+        toggle.send(());
+        assert_eq!(state.sample(), Door::Open);
+        lock.send(());
+        toggle.send(());
+        lock.send(());
+        assert_eq!(state.sample(), Door::Closed);
+        has_key.send(true);
+        lock.send(());
+        assert_eq!(state.sample(), Door::Locked);
+        toggle.send(());
+        lock.send(());
+        assert_eq!(state.sample(), Door::Closed);
+
+        assert_eq!(entered.sample(), [Door::Closed]);
+        assert_eq!(exited.sample(), [Door::Open, Door::Locked]);
+        assert_eq!(
+            invalid.sample(),
+            [
+                InvalidTransition::NoTransition {
+                    state: Door::Open,
+                    trigger: 1
+                },
+                InvalidTransition::Blocked {
+                    state: Door::Closed,
+                    trigger: 1
+                },
+                InvalidTransition::NoTransition {
+                    state: Door::Locked,
+                    trigger: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn fsm_reentrant() {
+        let toggle = Sink::<()>::new();
+        let door = Machine::builder(Door::Closed)
+            .state(Door::Open)
+            .transition(Door::Closed, Door::Open, &toggle.stream())
+            .transition(Door::Open, Door::Closed, &toggle.stream())
+            .build();
+        let transitions = door.transitions().collect::<Vec<_>>();
+        // closes the door again as soon as it's opened
+        let t = toggle.clone();
+        let opened = door.on_enter(Door::Open);
+        opened.observe(move |_| t.send(()));
+
+        toggle.send(());
+        assert_eq!(door.state().sample(), Door::Closed);
+        assert_eq!(
+            transitions.sample(),
+            [
+                Transition {
+                    from: Door::Closed,
+                    to: Door::Open
+                },
+                Transition {
+                    from: Door::Open,
+                    to: Door::Closed
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn fsm_concurrent_order() {
+        use std::thread;
+
+        let toggle = Sink::<()>::new();
+        let door = Machine::builder(Door::Closed)
+            .state(Door::Open)
+            .transition(Door::Closed, Door::Open, &toggle.stream())
+            .transition(Door::Open, Door::Closed, &toggle.stream())
+            .build();
+        let transitions = door.transitions().collect::<Vec<_>>();
+
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..500 {
+                        toggle.send(());
+                    }
+                });
+            }
+        });
+
+        let transitions = transitions.sample();
+        assert_eq!(transitions.len(), 2000);
+        assert_eq!(transitions[0].from, Door::Closed);
+        for pair in transitions.windows(2) {
+            assert_eq!(pair[0].to, pair[1].from);
+        }
+        assert_eq!(transitions[1999].to, door.state().sample());
+    }
+
+    #[test]
+    #[should_panic(expected = "undeclared state")]
+    fn fsm_undeclared() {
+        let sink = Sink::<()>::new();
+        Builder::new(Door::Closed)
+            .transition(Door::Closed, Door::Open, &sink.stream())
+            .build();
+    }
+}
@@ -2,11 +2,295 @@
 
 use crate::stream::Stream;
 use crate::sync::Mutex;
-use std::future::Future;
-use std::mem;
-use std::pin::Pin;
-use std::sync::Arc;
-use std::task::{Context, Poll, Waker};
+use crate::types::{CallbackHandle, Callbacks, Watcher};
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::{Arc, Weak};
+use core::fmt;
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+
+#[cfg(feature = "std")]
+use crate::clock::Scheduler;
+#[cfg(feature = "std")]
+use std::task::Wake;
+#[cfg(feature = "std")]
+use std::thread;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+/// A boxed future that can be sent to another thread.
+pub type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Runs futures in the background.
+///
+/// It's implemented for any `Fn(BoxFuture)`, so a closure can be used as a spawner. With the
+/// `tokio` feature enabled it's also implemented for `tokio::runtime::Handle`.
+pub trait Spawn: Send + Sync {
+    /// Starts running a future.
+    fn spawn(&self, future: BoxFuture);
+}
+
+impl<F: Fn(BoxFuture) + Send + Sync> Spawn for F {
+    #[inline]
+    fn spawn(&self, future: BoxFuture) {
+        self(future)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Spawn for tokio::runtime::Handle {
+    #[inline]
+    fn spawn(&self, future: BoxFuture) {
+        tokio::runtime::Handle::spawn(self, future);
+    }
+}
+
+/// Determines how `Stream::map_async` runs the futures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Concurrency {
+    /// Up to `n` futures run at the same time, and the results are sent as soon as they're ready.
+    Unordered(usize),
+    /// Up to `n` futures run at the same time, and the results are sent in the same order as the
+    /// input values.
+    Ordered(usize),
+    /// Only the future of the latest value runs. A new value cancels the running future.
+    LatestOnly,
+}
+
+/// Cancels a running future.
+#[derive(Debug, Default)]
+struct CancelToken {
+    cancelled: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl CancelToken {
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        let waker = self.waker.lock().take();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// A future that stops early when it's token is cancelled.
+struct Cancellable<F> {
+    future: Pin<Box<F>>,
+    token: Arc<CancelToken>,
+}
+
+impl<F: Future> Future for Cancellable<F> {
+    type Output = Option<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        if self.token.is_cancelled() {
+            return Poll::Ready(None);
+        }
+        *self.token.waker.lock() = Some(ctx.waker().clone());
+        // the token could have been cancelled before the waker was stored
+        if self.token.is_cancelled() {
+            return Poll::Ready(None);
+        }
+        self.future.as_mut().poll(ctx).map(Some)
+    }
+}
+
+/// Runs a closure when dropped, unless it's cancelled.
+struct OnDrop<G: FnOnce()>(Option<G>);
+
+impl<G: FnOnce()> OnDrop<G> {
+    fn new(f: G) -> Self {
+        OnDrop(Some(f))
+    }
+
+    fn cancel(mut self) {
+        self.0 = None;
+    }
+}
+
+impl<G: FnOnce()> Drop for OnDrop<G> {
+    fn drop(&mut self) {
+        if let Some(f) = self.0.take() {
+            f()
+        }
+    }
+}
+
+/// The mutable state of an async map.
+struct AsyncState<T, R> {
+    /// Values waiting for a free slot, with their sequence number.
+    waiting: VecDeque<(u64, T)>,
+    /// Amount of futures running.
+    running: usize,
+    /// Sequence number of the next input value.
+    next_seq: u64,
+    /// Sequence number of the next result to send in ordered mode.
+    next_out: u64,
+    /// Results waiting for the previous ones to finish in ordered mode.
+    finished: BTreeMap<u64, Option<R>>,
+    /// The token of the running future in latest-only mode.
+    current: Option<Arc<CancelToken>>,
+    /// Results ready to be sent.
+    output: VecDeque<R>,
+}
+
+/// Runs the futures created by `Stream::map_async`.
+pub(crate) struct AsyncMap<F, S, T, R> {
+    f: F,
+    spawner: S,
+    concurrency: Concurrency,
+    state: Mutex<AsyncState<T, R>>,
+    sending: AtomicBool,
+    target: Weak<Callbacks<R>>,
+}
+
+impl<F, Fut, S, T, R> AsyncMap<F, S, T, R>
+where
+    F: Fn(T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = R> + Send + 'static,
+    S: Spawn + 'static,
+    T: Send + 'static,
+    R: Send + 'static,
+{
+    /// Creates an async map that sends the results into `target`.
+    pub(crate) fn new(
+        f: F,
+        concurrency: Concurrency,
+        spawner: S,
+        target: Weak<Callbacks<R>>,
+    ) -> Arc<Self> {
+        Arc::new(AsyncMap {
+            f,
+            spawner,
+            concurrency,
+            state: Mutex::new(AsyncState {
+                waiting: VecDeque::new(),
+                running: 0,
+                next_seq: 0,
+                next_out: 0,
+                finished: BTreeMap::new(),
+                current: None,
+                output: VecDeque::new(),
+            }),
+            sending: AtomicBool::new(false),
+            target,
+        })
+    }
+
+    /// Handles a value from the source stream.
+    ///
+    /// Returns `false` when the target stream was dropped.
+    pub(crate) fn push(self: &Arc<Self>, val: T) -> bool {
+        if self.target.strong_count() == 0 {
+            return false;
+        }
+        let mut state = self.state.lock();
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        match self.concurrency {
+            Concurrency::Unordered(limit) | Concurrency::Ordered(limit) => {
+                if state.running < limit.max(1) {
+                    state.running += 1;
+                    drop(state);
+                    self.start(seq, val, Default::default());
+                } else {
+                    state.waiting.push_back((seq, val));
+                }
+            }
+            Concurrency::LatestOnly => {
+                let token = Arc::new(CancelToken::default());
+                let stale = state.current.replace(token.clone());
+                drop(state);
+                if let Some(stale) = stale {
+                    stale.cancel();
+                }
+                self.start(seq, val, token);
+            }
+        }
+        true
+    }
+
+    /// Spawns the future for a value.
+    fn start(self: &Arc<Self>, seq: u64, val: T, token: Arc<CancelToken>) {
+        let future = Cancellable {
+            future: Box::pin((self.f)(val)),
+            token,
+        };
+        let this = self.clone();
+        // frees the slot without a result if the future panics
+        let guard = OnDrop::new(move || this.finish(seq, None));
+        let this = self.clone();
+        self.spawner.spawn(Box::pin(async move {
+            let guard = guard;
+            let result = future.await;
+            guard.cancel();
+            this.finish(seq, result);
+        }));
+    }
+
+    /// Handles the result of a future, and starts the next waiting value.
+    fn finish(self: &Arc<Self>, seq: u64, result: Option<R>) {
+        let next = {
+            let mut state = self.state.lock();
+            match self.concurrency {
+                Concurrency::Unordered(_) => state.output.extend(result),
+                Concurrency::Ordered(_) => {
+                    // the futures that panicked are skipped
+                    state.finished.insert(seq, result);
+                    loop {
+                        let next_out = state.next_out;
+                        match state.finished.remove(&next_out) {
+                            Some(val) => state.output.extend(val),
+                            None => break,
+                        }
+                        state.next_out += 1;
+                    }
+                }
+                // results from stale values are discarded
+                Concurrency::LatestOnly if seq + 1 == state.next_seq => state.output.extend(result),
+                Concurrency::LatestOnly => (),
+            }
+            state.running = state.running.saturating_sub(1);
+            let next = state.waiting.pop_front();
+            if next.is_some() {
+                state.running += 1;
+            }
+            next
+        };
+        if let Some((seq, val)) = next {
+            self.start(seq, val, Default::default());
+        }
+        self.flush();
+    }
+
+    /// Sends the results that are ready.
+    ///
+    /// Only one thread sends at a time, so the results keep their order.
+    fn flush(&self) {
+        while !self.sending.swap(true, Ordering::SeqCst) {
+            loop {
+                let next = self.state.lock().output.pop_front();
+                match next {
+                    Some(val) => with_weak!(self.target, |cb| cb.call(val)),
+                    None => break,
+                };
+            }
+            self.sending.store(false, Ordering::SeqCst);
+            if self.state.lock().output.is_empty() {
+                break;
+            }
+        }
+    }
+}
 
 /// The state a stream future.
 #[derive(Debug)]
@@ -32,21 +316,34 @@ impl<T> Default for StreamFutureStorage<T> {
     }
 }
 
+/// Decides which values complete a stream future.
+type Predicate<T> = Arc<dyn Fn(&T) -> bool + Send + Sync>;
+
 /// A future that waits for a stream value.
 ///
-/// This is created by `Stream::next`.
-#[derive(Debug)]
+/// This is created by `Stream::next` and `Stream::next_matching`. Dropping the future removes it's
+/// observer from the stream right away, so futures that lose a `select!` don't pile up.
 pub struct StreamFuture<T> {
     storage: Arc<Mutex<StreamFutureStorage<T>>>,
     stream: Stream<T>,
+    pred: Option<Predicate<T>>,
+    observer: Mutex<Option<CallbackHandle<T>>>,
 }
 
 impl<T: Clone + Send + 'static> StreamFuture<T> {
     /// Creates a future that returns the next value sent to this stream.
+    #[inline]
     pub(crate) fn new(stream: Stream<T>) -> Self {
+        Self::with_predicate(stream, None)
+    }
+
+    /// Creates a future that returns the next value sent to this stream that matches a predicate.
+    pub(crate) fn with_predicate(stream: Stream<T>, pred: Option<Predicate<T>>) -> Self {
         let this = StreamFuture {
             storage: Default::default(),
             stream,
+            pred,
+            observer: Mutex::new(None),
         };
         this.register_callback();
         this
@@ -55,16 +352,24 @@ impl<T: Clone + Send + 'static> StreamFuture<T> {
     /// Registers the stream observer that will update this future.
     fn register_callback(&self) {
         let weak = Arc::downgrade(&self.storage);
-        self.stream.observe(move |val| {
+        let pred = self.pred.clone();
+        let handle = self.stream.observe_removable(move |val| {
+            if pred.as_ref().is_some_and(|pred| !pred(&val)) {
+                return true;
+            }
             if let Some(st) = weak.upgrade() {
-                let mut storage = st.lock();
-                storage.value = FutureValue::Ready(val.into_owned());
-                if let Some(waker) = storage.waker.take() {
+                let waker = {
+                    let mut storage = st.lock();
+                    storage.value = FutureValue::Ready(val.into_owned());
+                    storage.waker.take()
+                };
+                if let Some(waker) = waker {
                     waker.wake();
                 }
             }
             false
         });
+        *self.observer.lock() = Some(handle);
     }
 
     /// Obtains the source stream.
@@ -80,7 +385,8 @@ impl<T: Clone + Send + 'static> StreamFuture<T> {
     /// This allows awaiting for multiple values without having to create and allocate multiple
     /// future objects.
     ///
-    /// Calling this on a pending (or ready but unread) future will have no effect.
+    /// Calling this on a pending (or ready but unread) future will have no effect. The values sent
+    /// before the future is reloaded are lost, so use a `StreamReceiver` to get all of them.
     pub fn reload(&self) {
         let mut storage = self.storage.lock();
         if let FutureValue::Finished = storage.value {
@@ -88,6 +394,52 @@ impl<T: Clone + Send + 'static> StreamFuture<T> {
             self.register_callback();
         }
     }
+
+    /// Limits the time this future waits for a value.
+    ///
+    /// The resulting future returns `None` if the scheduler's timer fires before the stream sends
+    /// a value.
+    #[cfg(feature = "std")]
+    pub fn timeout<S: Scheduler>(self, duration: Duration, scheduler: S) -> Timeout<T> {
+        let timer = Arc::new(Mutex::new(TimerState::default()));
+        let weak = Arc::downgrade(&timer);
+        scheduler.schedule(
+            duration,
+            Box::new(move || {
+                if let Some(timer) = weak.upgrade() {
+                    let waker = {
+                        let mut timer = timer.lock();
+                        timer.expired = true;
+                        timer.waker.take()
+                    };
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }),
+        );
+        Timeout {
+            future: self,
+            timer,
+        }
+    }
+}
+
+impl<T> Drop for StreamFuture<T> {
+    fn drop(&mut self) {
+        if let Some(handle) = self.observer.lock().take() {
+            self.stream.unobserve(handle);
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for StreamFuture<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamFuture")
+            .field("storage", &self.storage)
+            .field("stream", &self.stream)
+            .finish()
+    }
 }
 
 impl<T> Future for StreamFuture<T> {
@@ -114,6 +466,220 @@ impl<T> Future for StreamFuture<T> {
 
 impl<T> Unpin for StreamFuture<T> {}
 
+/// The values waiting on a stream receiver.
+#[derive(Debug)]
+struct ReceiverQueue<T> {
+    values: VecDeque<T>,
+    waker: Option<Waker>,
+}
+
+impl<T: Send> Watcher for Mutex<ReceiverQueue<T>> {
+    /// Wakes the reader so it can see that the stream was closed.
+    fn closed(&self) {
+        let waker = self.lock().waker.take();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Receives the values of a stream through a queue.
+///
+/// This is created by `Stream::receiver`. Unlike `StreamFuture`, it stays registered on the
+/// stream for it's whole lifetime, so no values are lost between reads. The values can be read
+/// asynchronously with `recv`, or synchronously with `try_recv` and `recv_timeout`.
+///
+/// The queue has no bound, so the values pile up in memory if they aren't read. Use
+/// `Stream::buffered` or a bounded channel when the reader can fall behind.
+///
+/// The stream is closed when all the sinks that feed it are dropped, including the `Sender`s kept
+/// by the closures of `Stream::map_n` and `Stream::scan_n`. After that, the reads return the
+/// values left on the queue and then `None`. Streams fed from other threads or futures (like
+/// `Stream::buffered` and `Stream::map_async`) and `Stream::switch` are never closed.
+#[derive(Debug)]
+pub struct StreamReceiver<T> {
+    queue: Arc<Mutex<ReceiverQueue<T>>>,
+    stream: Stream<T>,
+    observer: Option<CallbackHandle<T>>,
+}
+
+impl<T: Clone + Send + 'static> StreamReceiver<T> {
+    /// Creates a receiver that queues the values sent to this stream.
+    pub(crate) fn new(stream: Stream<T>) -> Self {
+        let queue = Arc::new(Mutex::new(ReceiverQueue {
+            values: VecDeque::new(),
+            waker: None,
+        }));
+        let weak = Arc::downgrade(&queue);
+        let watcher: Weak<dyn Watcher> = weak.clone();
+        stream.watch_senders(&watcher);
+        let observer = stream.observe_removable(move |val| {
+            with_weak!(weak, |queue| {
+                let waker = {
+                    let mut queue = queue.lock();
+                    queue.values.push_back(val.into_owned());
+                    queue.waker.take()
+                };
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            })
+        });
+        StreamReceiver {
+            queue,
+            stream,
+            observer: Some(observer),
+        }
+    }
+}
+
+impl<T> StreamReceiver<T> {
+    /// Creates a future that returns the next value, or `None` if the stream was closed.
+    #[inline]
+    pub fn recv(&mut self) -> Recv<'_, T> {
+        Recv {
+            queue: &self.queue,
+            stream: &self.stream,
+        }
+    }
+
+    /// Takes the next value if there is one waiting.
+    #[inline]
+    pub fn try_recv(&mut self) -> Option<T> {
+        self.queue.lock().values.pop_front()
+    }
+
+    /// Waits for the next value, blocking the current thread.
+    ///
+    /// Returns `None` if no value arrives before the timeout, or if the stream was closed.
+    #[cfg(feature = "std")]
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Option<T> {
+        let deadline = Instant::now() + timeout;
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        loop {
+            {
+                let mut queue = self.queue.lock();
+                if let Some(val) = queue.values.pop_front() {
+                    return Some(val);
+                }
+                queue.waker = Some(waker.clone());
+                // checked after the waker is set, so the closing can't be missed
+                if !self.stream.is_open() {
+                    return None;
+                }
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                self.queue.lock().waker = None;
+                return None;
+            }
+            thread::park_timeout(deadline - now);
+        }
+    }
+
+    /// Gets the amount of values waiting.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.lock().values.len()
+    }
+
+    /// Checks if there are no values waiting.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Obtains the source stream.
+    #[inline]
+    pub fn get_source(&self) -> &Stream<T> {
+        &self.stream
+    }
+}
+
+impl<T> Drop for StreamReceiver<T> {
+    fn drop(&mut self) {
+        if let Some(handle) = self.observer.take() {
+            self.stream.unobserve(handle);
+        }
+    }
+}
+
+/// Wakes a thread blocked on `StreamReceiver::recv_timeout`.
+#[cfg(feature = "std")]
+struct ThreadWaker(thread::Thread);
+
+#[cfg(feature = "std")]
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark()
+    }
+}
+
+/// A future that waits for the next value of a `StreamReceiver`.
+///
+/// This is created by `StreamReceiver::recv`.
+#[derive(Debug)]
+pub struct Recv<'a, T> {
+    queue: &'a Mutex<ReceiverQueue<T>>,
+    stream: &'a Stream<T>,
+}
+
+impl<T> Future for Recv<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        let mut queue = self.queue.lock();
+        match queue.values.pop_front() {
+            Some(val) => Poll::Ready(Some(val)),
+            None => {
+                queue.waker = Some(ctx.waker().clone());
+                // checked after the waker is set, so the closing can't be missed
+                if self.stream.is_open() {
+                    Poll::Pending
+                } else {
+                    Poll::Ready(None)
+                }
+            }
+        }
+    }
+}
+
+/// The state of a timeout timer.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+struct TimerState {
+    expired: bool,
+    waker: Option<Waker>,
+}
+
+/// A stream future with a time limit.
+///
+/// This is created by `StreamFuture::timeout`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct Timeout<T> {
+    future: StreamFuture<T>,
+    timer: Arc<Mutex<TimerState>>,
+}
+
+#[cfg(feature = "std")]
+impl<T> Future for Timeout<T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        if let Poll::Ready(val) = Pin::new(&mut self.future).poll(ctx) {
+            return Poll::Ready(Some(val));
+        }
+        let mut timer = self.timer.lock();
+        if timer.expired {
+            Poll::Ready(None)
+        } else {
+            timer.waker = Some(ctx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -153,4 +719,222 @@ mod tests {
         sink.send(13);
         assert_eq!(block_on(&mut future), 13);
     }
+
+    #[test]
+    fn next_matching() {
+        let sink = Sink::new();
+        let future = sink.stream().next_matching(|n| n % 2 == 0);
+
+        sink.feed(vec![1, 3, 4, 5]);
+        assert_eq!(block_on(future), 4);
+    }
+
+    #[test]
+    fn drop_unregisters() {
+        let sink = Sink::<i32>::new();
+        let stream = sink.stream();
+        let future = stream.next();
+        let other = stream.next();
+        drop(future);
+        // the observer is removed without waiting for a value
+        assert_eq!(stream.stats().dropped, 1);
+
+        sink.send(1);
+        assert_eq!(block_on(other), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn timeout() {
+        use crate::clock::ThreadScheduler;
+        use std::time::Duration;
+
+        let sink = Sink::<i32>::new();
+        let future = sink
+            .stream()
+            .next()
+            .timeout(Duration::from_millis(20), ThreadScheduler);
+        assert_eq!(block_on(future), None);
+        assert_eq!(sink.stream().stats().dropped, 1);
+
+        let future = sink
+            .stream()
+            .next()
+            .timeout(Duration::from_secs(1), ThreadScheduler);
+        sink.send(1);
+        assert_eq!(block_on(future), Some(1));
+    }
+
+    #[test]
+    fn receiver() {
+        let sink = Sink::new();
+        let mut rx = sink.stream().receiver();
+
+        sink.feed(vec![1, 2, 3]);
+        assert_eq!(rx.len(), 3);
+        assert_eq!(block_on(rx.recv()), Some(1));
+        // values sent between reads aren't lost
+        sink.send(4);
+        assert_eq!(rx.try_recv(), Some(2));
+        assert_eq!(block_on(rx.recv()), Some(3));
+        assert_eq!(block_on(rx.recv()), Some(4));
+        assert_eq!(rx.try_recv(), None);
+
+        drop(rx);
+        assert_eq!(sink.stream().stats().dropped, 1);
+    }
+
+    #[test]
+    fn receiver_closed() {
+        use futures::task::noop_waker_ref;
+
+        let sink1 = Sink::new();
+        let sink2 = Sink::new();
+        let mut rx = sink1
+            .stream()
+            .merge(&sink2.stream())
+            .map(|x| *x * 2)
+            .receiver();
+        let mut ctx = Context::from_waker(noop_waker_ref());
+
+        sink1.send(1);
+        drop(sink1);
+        // the other sink can still send
+        assert_eq!(block_on(rx.recv()), Some(2));
+        assert_eq!(Pin::new(&mut rx.recv()).poll(&mut ctx), Poll::Pending);
+
+        let copy = sink2.clone();
+        drop(sink2);
+        copy.send(2);
+        assert_eq!(
+            Pin::new(&mut rx.recv()).poll(&mut ctx),
+            Poll::Ready(Some(4))
+        );
+        assert_eq!(Pin::new(&mut rx.recv()).poll(&mut ctx), Poll::Pending);
+        copy.send(3);
+        drop(copy);
+        // the values left are read before the end
+        assert_eq!(block_on(rx.recv()), Some(6));
+        assert_eq!(block_on(rx.recv()), None);
+        assert_eq!(rx.try_recv(), None);
+
+        assert_eq!(block_on(Stream::<i32>::never().receiver().recv()), None);
+    }
+
+    #[test]
+    fn receiver_closed_map_n() {
+        use futures::task::noop_waker_ref;
+
+        let sink = Sink::<i32>::new();
+        let stored = Arc::new(Mutex::new(None));
+        let st = stored.clone();
+        let mut rx = sink
+            .stream()
+            .map_n(move |_, sender| *st.lock() = Some(sender))
+            .receiver();
+        let mut ctx = Context::from_waker(noop_waker_ref());
+
+        sink.send(1);
+        drop(sink);
+        // the stored sender can still send
+        assert_eq!(Pin::new(&mut rx.recv()).poll(&mut ctx), Poll::Pending);
+        let sender = stored.lock().take().unwrap();
+        sender.send(10);
+        assert_eq!(block_on(rx.recv()), Some(10));
+        assert_eq!(Pin::new(&mut rx.recv()).poll(&mut ctx), Poll::Pending);
+        drop(sender);
+        assert_eq!(block_on(rx.recv()), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn receiver_closed_wakes() {
+        use std::thread;
+        use std::time::Duration;
+
+        let sink = Sink::<i32>::new();
+        let mut rx = sink.stream().receiver();
+        let mut rx2 = sink.stream().receiver();
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            drop(sink);
+        });
+        assert_eq!(block_on(rx.recv()), None);
+        assert_eq!(rx2.recv_timeout(Duration::from_secs(60)), None);
+        t.join().unwrap();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn receiver_never_closed() {
+        use crate::stream::StreamLoop;
+        use crate::types::OverflowPolicy;
+        use std::time::Duration;
+
+        // a loop could still be defined
+        let sink = Sink::<i32>::new();
+        let feedback = StreamLoop::new();
+        let mut rx = sink.stream().merge(&feedback.stream()).receiver();
+        drop(sink);
+        assert_eq!(rx.recv_timeout(Duration::from_millis(10)), None);
+        assert!(rx.get_source().is_open());
+        drop(feedback);
+        assert!(!rx.get_source().is_open());
+
+        // queued values can arrive after the sink is dropped
+        let sink = Sink::<i32>::new();
+        let (buffered, _) = sink.stream().buffered(4, OverflowPolicy::Block);
+        let rx = buffered.receiver();
+        drop(sink);
+        assert!(rx.get_source().is_open());
+    }
+
+    #[test]
+    fn receiver_wake_unlocked() {
+        use alloc::task::Wake;
+        use core::sync::atomic::AtomicUsize;
+
+        // a waker that reads the queue it's woken from
+        struct LenWaker(Arc<Mutex<ReceiverQueue<i32>>>, AtomicUsize);
+
+        impl Wake for LenWaker {
+            fn wake(self: Arc<Self>) {
+                self.wake_by_ref()
+            }
+
+            fn wake_by_ref(self: &Arc<Self>) {
+                let len = self.0.lock().values.len();
+                self.1.store(len, Ordering::SeqCst);
+            }
+        }
+
+        let sink = Sink::new();
+        let mut rx = sink.stream().receiver();
+        let len_waker = Arc::new(LenWaker(rx.queue.clone(), AtomicUsize::new(0)));
+        let waker = Waker::from(len_waker.clone());
+        let mut ctx = Context::from_waker(&waker);
+
+        assert_eq!(Pin::new(&mut rx.recv()).poll(&mut ctx), Poll::Pending);
+        sink.send(5);
+        assert_eq!(len_waker.1.load(Ordering::SeqCst), 1);
+        assert_eq!(rx.try_recv(), Some(5));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn receiver_timeout() {
+        use std::thread;
+        use std::time::Duration;
+
+        let sink = Sink::new();
+        let mut rx = sink.stream().receiver();
+        assert_eq!(rx.recv_timeout(Duration::from_millis(10)), None);
+
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            sink.send(42);
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Some(42));
+        t.join().unwrap();
+    }
 }
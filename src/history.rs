@@ -0,0 +1,361 @@
+//! Signals with undo/redo history.
+//!
+//! A `HistorySignal` is created by `Stream::fold_with_history` or `Stream::fold_with_commands`.
+//! It works like a signal created by `Stream::fold`, but it remembers the previous states so they
+//! can be restored by sending an event into the `undo` sink. Undone states can be restored again
+//! with the `redo` sink, until a new event arrives from the stream and the redo history is
+//! discarded.
+//!
+//! There are two ways to store the history:
+//!
+//! - Snapshots (`fold_with_history`): a copy of the accumulator is saved on every event. This is
+//!   simple and works with any fold function, but it's costly for large states.
+//! - Commands (`fold_with_commands`): the stream events are saved instead, and the changes are
+//!   reverted with an inverse function. This keeps the history small even for large states.
+//!
+//! # Example
+//! ```
+//! use frappe::Sink;
+//!
+//! let sink = Sink::<String>::new();
+//! let text = sink.stream().fold_with_history(String::new(), |s, c| s + c.as_str(), 100);
+//!
+//! sink.send("hello".to_string());
+//! sink.send(" world".to_string());
+//! assert_eq!(text.sample(), "hello world");
+//!
+//! text.undo();
+//! assert_eq!(text.sample(), "hello");
+//! assert!(text.can_redo().sample());
+//!
+//! text.redo();
+//! assert_eq!(text.sample(), "hello world");
+//! ```
+
+use crate::signal::Signal;
+use crate::stream::{Sink, Stream};
+use crate::sync::Mutex;
+use crate::types::{MaybeOwned, Storage};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// The history entries of an accumulator.
+struct History<E> {
+    past: VecDeque<E>,
+    future: Vec<E>,
+    capacity: usize,
+}
+
+impl<E> History<E> {
+    /// Records the entry of a new event, discarding the redo history.
+    fn record(&mut self, entry: E) {
+        self.future.clear();
+        if self.capacity > 0 {
+            if self.past.len() == self.capacity {
+                self.past.pop_front();
+            }
+            self.past.push_back(entry);
+        }
+    }
+
+    /// Moves back one step.
+    ///
+    /// Does nothing if the state was lost by a panic. The state only changes while the history is
+    /// locked, so the entry can't be lost between the check and the update.
+    fn undo<A, F>(&mut self, state: &Storage<A>, f: F)
+    where
+        F: FnOnce(A, E) -> (A, E),
+    {
+        if state.try_borrow().is_err() {
+            return;
+        }
+        if let Some(entry) = self.past.pop_back() {
+            if let Ok(entry) = state.try_replace_with(|st| f(st, entry)) {
+                self.future.push(entry);
+            }
+        }
+    }
+
+    /// Moves forward one step.
+    fn redo<A, F>(&mut self, state: &Storage<A>, f: F)
+    where
+        F: FnOnce(A, E) -> (A, E),
+    {
+        if state.try_borrow().is_err() {
+            return;
+        }
+        if let Some(entry) = self.future.pop() {
+            if let Ok(entry) = state.try_replace_with(|st| f(st, entry)) {
+                self.past.push_back(entry);
+            }
+        }
+    }
+}
+
+/// A signal that can undo and redo the changes made by a stream.
+pub struct HistorySignal<A> {
+    value: Signal<A>,
+    can_undo: Signal<bool>,
+    can_redo: Signal<bool>,
+    undo: Sink<()>,
+    redo: Sink<()>,
+}
+
+impl<A: Clone + Send + Sync + 'static> HistorySignal<A> {
+    /// Creates a history signal from a stream.
+    ///
+    /// `on_event` applies an event and returns the history entry that reverts it. `on_undo` and
+    /// `on_redo` apply a history entry and return the entry that goes in the opposite direction.
+    ///
+    /// Like `Stream::fold`, the state is lost if one of the closures panics, and sampling the
+    /// signal fails from then on.
+    pub(crate) fn new<T, E, F, U, R>(
+        stream: &Stream<T>,
+        operator: &'static str,
+        initial: A,
+        capacity: usize,
+        on_event: F,
+        on_undo: U,
+        on_redo: R,
+    ) -> Self
+    where
+        T: 'static,
+        E: Send + 'static,
+        F: Fn(A, MaybeOwned<'_, T>) -> (A, E) + Send + Sync + 'static,
+        U: Fn(A, E) -> (A, E) + Send + Sync + 'static,
+        R: Fn(A, E) -> (A, E) + Send + Sync + 'static,
+    {
+        let state = Arc::new(Storage::with_node(initial, operator, stream.node_id()));
+        // the history lock is held while the state changes, so both stay in sync
+        let history = Arc::new(Mutex::new(History {
+            past: VecDeque::new(),
+            future: Vec::new(),
+            capacity,
+        }));
+        let undo = Sink::new();
+        let redo = Sink::new();
+
+        // the observers are removed once the state is dropped
+        let (h, weak) = (history.clone(), Arc::downgrade(&state));
+        stream.observe(move |arg| {
+            with_weak!(weak, |st| {
+                let mut h = h.lock();
+                if let Ok(entry) = st.try_replace_with(|state| on_event(state, arg)) {
+                    h.record(entry);
+                }
+            })
+        });
+        let (h, weak) = (history.clone(), Arc::downgrade(&state));
+        undo.stream()
+            .observe(move |_| with_weak!(weak, |st| h.lock().undo(&st, &on_undo)));
+        let (h, weak) = (history.clone(), Arc::downgrade(&state));
+        redo.stream()
+            .observe(move |_| with_weak!(weak, |st| h.lock().redo(&st, &on_redo)));
+
+        let value = Signal::from_storage(state, stream.clone());
+        let h = history.clone();
+        let can_undo = Signal::from_fn(move || !h.lock().past.is_empty());
+        let h = history;
+        let can_redo = Signal::from_fn(move || !h.lock().future.is_empty());
+
+        HistorySignal {
+            value,
+            can_undo,
+            can_redo,
+            undo,
+            redo,
+        }
+    }
+}
+
+impl<A> HistorySignal<A> {
+    /// Samples the current value.
+    #[inline]
+    pub fn sample(&self) -> A {
+        self.value.sample()
+    }
+
+    /// Gets the signal with the current value.
+    #[inline]
+    pub fn signal(&self) -> &Signal<A> {
+        &self.value
+    }
+
+    /// Gets a signal that is `true` when there are changes to undo.
+    #[inline]
+    pub fn can_undo(&self) -> &Signal<bool> {
+        &self.can_undo
+    }
+
+    /// Gets a signal that is `true` when there are undone changes to redo.
+    #[inline]
+    pub fn can_redo(&self) -> &Signal<bool> {
+        &self.can_redo
+    }
+
+    /// Gets the sink that undoes the last change every time it receives an event.
+    #[inline]
+    pub fn undo_sink(&self) -> &Sink<()> {
+        &self.undo
+    }
+
+    /// Gets the sink that redoes the last undone change every time it receives an event.
+    #[inline]
+    pub fn redo_sink(&self) -> &Sink<()> {
+        &self.redo
+    }
+
+    /// Undoes the last change.
+    #[inline]
+    pub fn undo(&self) {
+        self.undo.send(())
+    }
+
+    /// Redoes the last undone change.
+    #[inline]
+    pub fn redo(&self) {
+        self.redo.send(())
+    }
+}
+
+impl<A> Clone for HistorySignal<A> {
+    fn clone(&self) -> Self {
+        HistorySignal {
+            value: self.value.clone(),
+            can_undo: self.can_undo.clone(),
+            can_redo: self.can_redo.clone(),
+            undo: self.undo.clone(),
+            redo: self.redo.clone(),
+        }
+    }
+}
+
+impl<A> From<HistorySignal<A>> for Signal<A> {
+    #[inline]
+    fn from(h: HistorySignal<A>) -> Self {
+        h.value
+    }
+}
+
+impl<A> fmt::Debug for HistorySignal<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HistorySignal")
+            .field("value", &self.value)
+            .field("undo", &self.undo)
+            .field("redo", &self.redo)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Sink;
+
+    #[test]
+    fn history_snapshots() {
+        let sink = Sink::new();
+        let sum = sink.stream().fold_with_history(0, |a, n| a + *n, 3);
+        assert!(!sum.can_undo().sample());
+        assert!(!sum.can_redo().sample());
+
+        sink.feed(1..=5);
+        assert_eq!(sum.sample(), 15);
+        assert!(sum.can_undo().sample());
+
+        // only the last 3 changes are remembered
+        for _ in 0..5 {
+            sum.undo();
+        }
+        assert_eq!(sum.sample(), 3);
+        assert!(!sum.can_undo().sample());
+        assert!(sum.can_redo().sample());
+
+        sum.redo_sink().send(());
+        assert_eq!(sum.sample(), 6);
+
+        // a new event discards the redo history
+        sink.send(100);
+        assert_eq!(sum.sample(), 106);
+        assert!(!sum.can_redo().sample());
+        sum.redo();
+        assert_eq!(sum.sample(), 106);
+        sum.undo();
+        sum.undo();
+        assert_eq!(sum.sample(), 3);
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Add(i32),
+        Neg,
+    }
+
+    #[test]
+    fn history_commands() {
+        let sink = Sink::new();
+        let apply = |a: i32, op: &Op| match op {
+            Op::Add(n) => a + n,
+            Op::Neg => -a,
+        };
+        let revert = |a: i32, op: &Op| match op {
+            Op::Add(n) => a - n,
+            Op::Neg => -a,
+        };
+        let value = sink.stream().fold_with_commands(0, apply, revert, 10);
+
+        sink.feed(vec![Op::Add(5), Op::Neg, Op::Add(2)]);
+        assert_eq!(value.sample(), -3);
+        value.undo();
+        assert_eq!(value.sample(), -5);
+        value.undo();
+        assert_eq!(value.sample(), 5);
+        value.redo();
+        assert_eq!(value.sample(), -5);
+
+        sink.send(Op::Add(10));
+        assert_eq!(value.sample(), 5);
+        assert!(!value.can_redo().sample());
+        value.undo();
+        value.undo();
+        value.undo();
+        assert_eq!(value.sample(), 0);
+        assert!(!value.can_undo().sample());
+
+        // zero capacity keeps no history
+        let value = sink.stream().fold_with_commands(0, apply, revert, 0);
+        sink.send(Op::Add(1));
+        value.undo();
+        assert_eq!(value.sample(), 1);
+        assert!(!value.can_undo().sample());
+    }
+
+    #[test]
+    fn history_panic() {
+        use crate::signal::SampleError;
+
+        let sink = Sink::new();
+        let sum = sink.stream().fold_with_history(
+            0,
+            |a, n: crate::types::MaybeOwned<'_, i32>| match *n {
+                n if n < 0 => panic!("negative value"),
+                n => a + n,
+            },
+            10,
+        );
+        sink.send(2);
+        assert_eq!(sum.signal().try_sample(), Ok(2));
+
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| sink.send(-1)));
+        assert!(res.is_err());
+        let err = SampleError::Poisoned;
+        assert_eq!(sum.signal().try_sample(), Err(err));
+        // later events don't panic, and the history is kept as it was
+        sink.send(3);
+        sum.undo();
+        sum.redo();
+        assert_eq!(sum.signal().try_sample(), Err(err));
+        assert!(sum.can_undo().sample());
+    }
+}
@@ -4,15 +4,51 @@
 //! Rust-idiomatic way to write interactive applications in a declarative way.
 //!
 //! See each module documentation for more details.
+//!
+//! # `no_std` support
+//! The library can be used on `no_std` targets that provide the `alloc` crate by disabling the
+//! default `std` feature. In that case the `spin` feature must be enabled to provide the locks.
+//! The channel, thread and parallel APIs are not available without `std`.
+//!
+//! # Tracing
+//! With the `tracing` feature enabled, every value sent into a `Sink` opens a `send` span (at debug
+//! level), and every stream operation it passes through opens a nested `hop` span (at trace level).
+//! The spans record the node id and name, the operator, the value type and whether the value was
+//! delivered owned or borrowed. Use `Stream::named` to make the streams easier to identify.
+//!
+//! # Tokio
+//! The `tokio` feature adds bridges to the tokio channels (`Signal::to_watch`,
+//! `Stream::to_broadcast` and `Sink::from_mpsc`), and a `clock::TokioScheduler` that runs the
+//! scheduled tasks (like the ones of `StreamFuture::timeout`) on the runtime's timers.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![warn(missing_docs)]
 
+extern crate alloc;
+
+#[cfg(not(any(feature = "std", feature = "spin")))]
+compile_error!("either the `std` or the `spin` feature must be enabled");
+
 #[macro_use]
 mod helpers;
+#[cfg(feature = "std")]
+pub mod clock;
+pub mod collection;
+#[cfg(feature = "std")]
+pub mod debug;
+pub mod fsm;
 pub mod futures;
+pub mod history;
 mod lift;
+pub mod local;
+#[cfg(feature = "serde")]
+pub mod persist;
+pub mod program;
+#[cfg(feature = "serde")]
+pub mod record;
 pub mod signal;
 pub mod stream;
 mod sync;
+mod trace;
 pub mod types;
 
 pub use crate::signal::Signal;
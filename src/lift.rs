@@ -35,9 +35,9 @@ macro_rules! signal_lift {
     });
 
     (@closure $body:expr ; $($args:pat)* , $($vars:ident)* ;) => {
-        $crate::Signal::from_fn(move || {
-            let ($($args),*) = ($($crate::Signal::sample(&$vars)),*);
-            $body
+        $crate::Signal::from_try_fn(move || {
+            let ($($args),*) = ($($crate::Signal::try_sample(&$vars)?),*);
+            Ok($body)
         })
     };
 
@@ -47,7 +47,7 @@ macro_rules! signal_lift {
     });
 
     (@expr $f:expr ; $($vars:ident)* ;) => {
-        $crate::Signal::from_fn(move || $f($($crate::Signal::sample(&$vars)),*))
+        $crate::Signal::from_try_fn(move || Ok($f($($crate::Signal::try_sample(&$vars)?),*)))
     };
 
     (@expr $f:expr ; $($vars:ident)* ; $sig:expr $(,$stail:expr)*) => ({
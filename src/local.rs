@@ -0,0 +1,36 @@
+//! Single-threaded versions of the Stream and Signal types.
+//!
+//! The regular `Stream` and `Signal` types require every closure to be `Send + Sync`, so values
+//! like `Rc` or GUI toolkit handles can't be captured by them. This module provides
+//! `LocalSink`, `LocalStream` and `LocalSignal`, which have the same API but are built on
+//! `Rc` + `RefCell` without any locking, so they can't be sent to other threads.
+//!
+//! The local types can interact with the thread-safe ones where it's sound to do so:
+//! a `Signal` can be sampled from a `LocalSignal` by using `LocalSignal::from_signal`, and a `LocalStream` can send it's values
+//! into a `Stream` by using `LocalStream::to_stream`.
+//!
+//! # Example
+//! ```
+//! use frappe::local::LocalSink;
+//! use std::rc::Rc;
+//!
+//! let name = Rc::new("counter");
+//! let sink = LocalSink::new();
+//! let text = sink
+//!     .stream()
+//!     .fold(0, |a, n| a + *n)
+//!     .map(move |n| format!("{}: {}", name, n));
+//!
+//! sink.feed(1..=3);
+//! assert_eq!(text.sample(), "counter: 6");
+//! ```
+
+mod callbacks;
+mod futures;
+mod signal;
+mod storage;
+mod stream;
+
+pub use self::futures::LocalStreamFuture;
+pub use self::signal::LocalSignal;
+pub use self::stream::{LocalSender, LocalSink, LocalStream};
@@ -0,0 +1,166 @@
+//! Callback container for LocalStream.
+//!
+//! This is the single-threaded version of `types::Callbacks`. It follows the same reentrancy
+//! rules: callbacks added while the list is being dispatched are queued and then appended after
+//! the dispatch finishes.
+
+use crate::types::MaybeOwned;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::fmt;
+
+/// The boxed closure type stored by `FnCell`.
+type CallbackFn<T> = dyn Fn(MaybeOwned<'_, T>) -> bool;
+
+/// Function that becomes uncallable after it returns false.
+struct FnCell<T> {
+    f: Box<CallbackFn<T>>,
+    alive: Cell<bool>,
+}
+
+impl<T> FnCell<T> {
+    /// Creates a new `FnCell` from the supplied closure.
+    fn new<F>(f: F) -> Self
+    where
+        F: Fn(MaybeOwned<'_, T>) -> bool + 'static,
+    {
+        FnCell {
+            f: Box::new(f),
+            alive: Cell::new(true),
+        }
+    }
+
+    /// Calls the stored function and updates it's callable status.
+    fn call(&self, arg: MaybeOwned<'_, T>) -> bool {
+        if self.alive.get() {
+            let is_alive = (self.f)(arg);
+            if !is_alive {
+                self.alive.set(false);
+            }
+            is_alive
+        } else {
+            false
+        }
+    }
+
+    /// Checks if this function can still be called.
+    fn is_alive(&self) -> bool {
+        self.alive.get()
+    }
+}
+
+impl<T> fmt::Debug for FnCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "FnCell {{ f: Fn@{:p}, alive: {:?} }}",
+            self.f, self.alive
+        )
+    }
+}
+
+/// A collection of callbacks.
+#[derive(Debug)]
+pub struct Callbacks<T> {
+    fs: RefCell<Vec<FnCell<T>>>,
+    /// Callbacks added while `fs` was borrowed.
+    queued: RefCell<Vec<FnCell<T>>>,
+}
+
+impl<T> Callbacks<T> {
+    /// Creates an empty callback list.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds a new closure to the callback list.
+    ///
+    /// If the list is being dispatched (for example, when called from inside a callback) the
+    /// closure is queued, and it will be added when the dispatch finishes.
+    pub fn push<F>(&self, cb: F)
+    where
+        F: Fn(MaybeOwned<'_, T>) -> bool + 'static,
+    {
+        let cell = FnCell::new(cb);
+        match self.fs.try_borrow_mut() {
+            Ok(mut fs) => fs.push(cell),
+            Err(_) => self.queued.borrow_mut().push(cell),
+        }
+    }
+
+    /// Sends an owned value.
+    ///
+    /// This sends a ref to the first N-1 callbacks, and the owned value to the last.
+    pub fn call_owned(&self, arg: T) {
+        self.dispatch(|fs| match fs.split_last() {
+            Some((last, rest)) => call_all(rest, &arg) & last.call(MaybeOwned::Owned(arg)),
+            None => true,
+        })
+    }
+
+    /// Sends a value by reference.
+    pub fn call_ref(&self, arg: &T) {
+        self.dispatch(|fs| call_all(fs, arg))
+    }
+
+    /// Sends a value.
+    #[inline]
+    pub fn call<'a>(&self, arg: impl Into<MaybeOwned<'a, T>>)
+    where
+        T: 'a,
+    {
+        match arg.into() {
+            MaybeOwned::Owned(v) => self.call_owned(v),
+            MaybeOwned::Borrowed(r) => self.call_ref(r),
+        }
+    }
+
+    /// Runs a dispatch function over the callback list.
+    ///
+    /// The dispatch function must return `false` if any of the callbacks died, so they can be
+    /// removed after the list borrow is released.
+    fn dispatch<F>(&self, f: F)
+    where
+        F: FnOnce(&[FnCell<T>]) -> bool,
+    {
+        let all_alive = f(&self.fs.borrow());
+        if !all_alive || !self.queued.borrow().is_empty() {
+            self.cleanup();
+        }
+    }
+
+    /// Removes the dead callbacks and appends the queued ones.
+    ///
+    /// If the list is still borrowed by an outer dispatch, the work is left to it.
+    fn cleanup(&self) {
+        if let Ok(mut fs) = self.fs.try_borrow_mut() {
+            fs.retain(FnCell::is_alive);
+            fs.append(&mut self.queued.borrow_mut());
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.fs.borrow().len() + self.queued.borrow().len()
+    }
+}
+
+impl<T> Default for Callbacks<T> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            fs: Default::default(),
+            queued: Default::default(),
+        }
+    }
+}
+
+/// Sends a value by reference to a list of callbacks.
+///
+/// Returns `false` if any of the callbacks died.
+fn call_all<T>(fs: &[FnCell<T>], arg: &T) -> bool {
+    fs.iter()
+        .map(|f| f.call(MaybeOwned::Borrowed(arg)))
+        .fold(true, |a, alive| a & alive)
+}
@@ -0,0 +1,133 @@
+//! Futures integration for local streams.
+
+use crate::local::stream::LocalStream;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+/// The state a stream future.
+#[derive(Debug)]
+enum FutureValue<T> {
+    Pending,
+    Ready(T),
+    Finished,
+}
+
+/// The storage of a stream future.
+#[derive(Debug)]
+struct StreamFutureStorage<T> {
+    value: FutureValue<T>,
+    waker: Option<Waker>,
+}
+
+impl<T> Default for StreamFutureStorage<T> {
+    fn default() -> Self {
+        StreamFutureStorage {
+            value: FutureValue::Pending,
+            waker: None,
+        }
+    }
+}
+
+/// A future that waits for a local stream value.
+///
+/// This is created by `LocalStream::next`.
+#[derive(Debug)]
+pub struct LocalStreamFuture<T> {
+    storage: Rc<RefCell<StreamFutureStorage<T>>>,
+    stream: LocalStream<T>,
+}
+
+impl<T: Clone + 'static> LocalStreamFuture<T> {
+    /// Creates a future that returns the next value sent to this stream.
+    pub(crate) fn new(stream: LocalStream<T>) -> Self {
+        let this = LocalStreamFuture {
+            storage: Default::default(),
+            stream,
+        };
+        this.register_callback();
+        this
+    }
+
+    /// Registers the stream observer that will update this future.
+    fn register_callback(&self) {
+        let weak = Rc::downgrade(&self.storage);
+        self.stream.observe(move |val| {
+            if let Some(st) = weak.upgrade() {
+                let waker = {
+                    let mut storage = st.borrow_mut();
+                    storage.value = FutureValue::Ready(val.into_owned());
+                    storage.waker.take()
+                };
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+            }
+            false
+        });
+    }
+
+    /// Obtains the source stream.
+    #[inline]
+    pub fn get_source(&self) -> &LocalStream<T> {
+        &self.stream
+    }
+
+    /// Reuses a finished future so it can wait for another value.
+    ///
+    /// This works like `StreamFuture::reload`.
+    pub fn reload(&self) {
+        let mut storage = self.storage.borrow_mut();
+        if let FutureValue::Finished = storage.value {
+            *storage = Default::default();
+            self.register_callback();
+        }
+    }
+}
+
+impl<T> Future for LocalStreamFuture<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, ctx: &mut Context) -> Poll<Self::Output> {
+        let mut storage = self.storage.borrow_mut();
+        match mem::replace(&mut storage.value, FutureValue::Pending) {
+            FutureValue::Ready(value) => {
+                storage.value = FutureValue::Finished;
+                Poll::Ready(value)
+            }
+            FutureValue::Pending => {
+                storage.waker = Some(ctx.waker().clone());
+                Poll::Pending
+            }
+            FutureValue::Finished => {
+                storage.value = FutureValue::Finished;
+                panic!("future polled again after completion");
+            }
+        }
+    }
+}
+
+impl<T> Unpin for LocalStreamFuture<T> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::local::LocalSink;
+    use futures::executor::block_on;
+
+    #[test]
+    fn local_future() {
+        let sink = LocalSink::new();
+        let mut future = sink.stream().next();
+
+        sink.send(42);
+        sink.send(13);
+        assert_eq!(block_on(&mut future), 42);
+
+        future.reload();
+        sink.send(7);
+        assert_eq!(block_on(&mut future), 7);
+    }
+}
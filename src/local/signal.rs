@@ -0,0 +1,260 @@
+//! The LocalSignal type.
+
+use crate::local::storage::Storage;
+use crate::local::stream::LocalStream;
+use crate::signal::Signal;
+use crate::types::MaybeOwned;
+use alloc::rc::Rc;
+use core::fmt;
+
+#[cfg(feature = "std")]
+use std::sync::mpsc;
+
+#[cfg(feature = "lazycell")]
+use lazycell::LazyCell;
+
+/// Represents a value that changes over time.
+///
+/// This is the single-threaded version of `Signal`.
+pub struct LocalSignal<T>(Rc<dyn Fn() -> T>);
+
+impl<T> LocalSignal<T> {
+    /// Creates a signal with constant value.
+    #[inline]
+    pub fn constant(val: T) -> Self
+    where
+        T: Clone + 'static,
+    {
+        LocalSignal::from_fn(move || val.clone())
+    }
+
+    /// Creates a signal that samples it's values from an external source.
+    #[inline]
+    pub fn from_fn<F>(f: F) -> Self
+    where
+        F: Fn() -> T + 'static,
+    {
+        LocalSignal(Rc::new(f))
+    }
+
+    /// Creates a signal from shared storage.
+    #[inline]
+    pub(crate) fn from_storage<S>(storage: Rc<Storage<T>>, source: S) -> Self
+    where
+        T: Clone + 'static,
+        S: 'static,
+    {
+        LocalSignal::from_fn(move || {
+            let _keepalive = &source;
+            storage.get()
+        })
+    }
+
+    /// Creates a local signal that samples a thread-safe signal.
+    #[inline]
+    pub fn from_signal(signal: Signal<T>) -> Self
+    where
+        T: 'static,
+    {
+        LocalSignal::from_fn(move || signal.sample())
+    }
+
+    /// Samples the value of the signal.
+    #[inline]
+    pub fn sample(&self) -> T {
+        self.0()
+    }
+
+    /// Maps a signal using the provided function.
+    pub fn map<F, R>(&self, f: F) -> LocalSignal<R>
+    where
+        F: Fn(T) -> R + 'static,
+        T: 'static,
+    {
+        let this = self.clone();
+        LocalSignal::from_fn(move || f(this.sample()))
+    }
+
+    /// Folds a signal using the provided function.
+    pub fn fold<A, F>(&self, initial: A, f: F) -> LocalSignal<A>
+    where
+        F: Fn(A, T) -> A + 'static,
+        T: 'static,
+        A: Clone + 'static,
+    {
+        let this = self.clone();
+        let storage = Storage::new(initial);
+        LocalSignal::from_fn(move || {
+            let val = this.sample();
+            storage.replace_fetch(|acc| f(acc, val))
+        })
+    }
+
+    /// Samples the value of this signal every time the trigger stream fires.
+    pub fn snapshot<S, F, R>(&self, trigger: &LocalStream<S>, f: F) -> LocalStream<R>
+    where
+        F: Fn(T, MaybeOwned<'_, S>) -> R + 'static,
+        T: 'static,
+        S: 'static,
+        R: 'static,
+    {
+        let this = self.clone();
+        trigger.map(move |t| f(this.sample(), t))
+    }
+
+    /// Stores the last value sent to a channel.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn from_channel(initial: T, rx: mpsc::Receiver<T>) -> Self
+    where
+        T: Clone + 'static,
+    {
+        Self::fold_channel(initial, rx, |_, v| v)
+    }
+
+    /// Creates a signal that folds the values from a channel.
+    #[cfg(feature = "std")]
+    pub fn fold_channel<V, F>(initial: T, rx: mpsc::Receiver<V>, f: F) -> Self
+    where
+        F: Fn(T, V) -> T + 'static,
+        T: Clone + 'static,
+        V: 'static,
+    {
+        let storage = Storage::new(initial);
+        LocalSignal::from_fn(move || {
+            if let Ok(first) = rx.try_recv() {
+                storage.replace_fetch(|old| {
+                    let acc = f(old, first);
+                    rx.try_iter().fold(acc, &f)
+                })
+            } else {
+                storage.get()
+            }
+        })
+    }
+
+    /// Creates a signal with a cyclic definition.
+    ///
+    /// This works like `Signal::cyclic`. Sampling the forward-declared signal will cause a panic.
+    #[cfg(feature = "lazycell")]
+    pub fn cyclic<F>(definition: F) -> Self
+    where
+        F: FnOnce(&LocalSignal<T>) -> LocalSignal<T>,
+        T: 'static,
+    {
+        let storage = Rc::new(LazyCell::new());
+        let st = storage.clone();
+        let sig = LocalSignal::from_fn(move || {
+            LocalSignal::sample(st.borrow().expect("sampled forward-declared Signal"))
+        });
+        storage.fill(definition(&sig)).ok().unwrap();
+        sig
+    }
+}
+
+impl<T: 'static> LocalSignal<LocalSignal<T>> {
+    /// Creates a new signal that samples the inner value of a nested signal.
+    pub fn switch(&self) -> LocalSignal<T> {
+        let this = self.clone();
+        LocalSignal::from_fn(move || this.sample().sample())
+    }
+}
+
+impl<T> Clone for LocalSignal<T> {
+    /// Creates a new signal that references the same value.
+    #[inline]
+    fn clone(&self) -> Self {
+        LocalSignal(self.0.clone())
+    }
+}
+
+impl<T: Default + 'static> Default for LocalSignal<T> {
+    /// Creates a constant signal with T's default value.
+    #[inline]
+    fn default() -> Self {
+        LocalSignal::from_fn(T::default)
+    }
+}
+
+impl<T: Clone + 'static> From<T> for LocalSignal<T> {
+    /// Creates a constant signal from T.
+    #[inline]
+    fn from(val: T) -> Self {
+        LocalSignal::constant(val)
+    }
+}
+
+impl<T> fmt::Debug for LocalSignal<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LocalSignal(Fn@{:p})", self.0)
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for LocalSignal<T> {
+    /// Samples the signal and formats the value.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.sample(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local::LocalSink;
+    use std::cell::Cell;
+
+    #[test]
+    fn local_signal_basic() {
+        let counter = Rc::new(Cell::new(1));
+        let counter_ = counter.clone();
+        let signal = LocalSignal::from_fn(move || counter_.get());
+        let double = signal.map(|a| a * 2);
+        let sum = signal.fold(0, |a, n| a + n);
+
+        assert_eq!(double.sample(), 2);
+        assert_eq!(sum.sample(), 1);
+        counter.set(13);
+        assert_eq!(double.sample(), 26);
+        assert_eq!(sum.sample(), 14);
+        assert_eq!(format!("{}", signal), "13");
+    }
+
+    #[test]
+    fn local_signal_snapshot() {
+        let sink = LocalSink::new();
+        let sig = sink.stream().fold(0, |a, n| a + *n);
+        let snap = sig.snapshot(&sink.stream(), |a, n| a * *n).hold(0);
+
+        sink.send(2);
+        assert_eq!(sig.sample(), 2);
+        assert_eq!(snap.sample(), 4);
+        sink.send(3);
+        assert_eq!(snap.sample(), 15);
+    }
+
+    #[test]
+    fn local_signal_from_signal() {
+        let sink = crate::Sink::new();
+        let local = LocalSignal::from_signal(sink.stream().hold(0));
+
+        sink.send(42);
+        assert_eq!(local.sample(), 42);
+    }
+
+    #[cfg(feature = "lazycell")]
+    #[test]
+    fn local_signal_cyclic() {
+        let sink = LocalSink::new();
+        let stream = sink.stream();
+        let sig = LocalSignal::cyclic(|prev| {
+            let prev = prev.clone();
+            stream.map(move |n| *n + prev.sample()).hold(1)
+        });
+
+        assert_eq!(sig.sample(), 1);
+        sink.send(2);
+        assert_eq!(sig.sample(), 3);
+        sink.send(4);
+        assert_eq!(sig.sample(), 7);
+    }
+}
@@ -0,0 +1,808 @@
+//! The LocalStream type.
+
+use crate::helpers::rc_and_weak;
+use crate::local::callbacks::Callbacks;
+use crate::local::futures::LocalStreamFuture;
+use crate::local::signal::LocalSignal;
+use crate::local::storage::Storage;
+use crate::stream::{Sink, Stream};
+use crate::types::{MaybeOwned, ObserveResult, SumType2};
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use core::any::Any;
+use core::cell::{Cell, RefCell};
+use core::ops::{Bound, RangeBounds};
+
+#[cfg(feature = "either")]
+use crate::types::Either;
+
+/// A source of events that feeds the local streams connected to it.
+///
+/// This is the single-threaded version of `Sink`.
+#[derive(Debug)]
+pub struct LocalSink<T> {
+    cbs: Rc<Callbacks<T>>,
+}
+
+impl<T> LocalSink<T> {
+    /// Creates a new sink.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a stream that receives the events sent to this sink.
+    #[inline]
+    pub fn stream(&self) -> LocalStream<T> {
+        LocalStream::new(self.cbs.clone(), Source::None)
+    }
+
+    /// Sends a value into the sink.
+    ///
+    /// The value will be distributed `N-1` times as reference and then one time by value,
+    /// where `N` is the amount of streams connected to this sink.
+    #[inline]
+    pub fn send<'a>(&self, val: impl Into<MaybeOwned<'a, T>>)
+    where
+        T: 'a,
+    {
+        self.cbs.call(val)
+    }
+
+    /// Sends multiple values into the sink.
+    #[inline]
+    pub fn feed<'a, I, U>(&self, iter: I)
+    where
+        I: IntoIterator<Item = U>,
+        U: Into<MaybeOwned<'a, T>>,
+        T: 'a,
+    {
+        for val in iter {
+            self.send(val)
+        }
+    }
+}
+
+impl<T> Default for LocalSink<T> {
+    /// Creates a new sink.
+    #[inline]
+    fn default() -> Self {
+        Self {
+            cbs: Default::default(),
+        }
+    }
+}
+
+impl<T> Clone for LocalSink<T> {
+    /// Creates a copy of this sink that references the same event source.
+    #[inline]
+    fn clone(&self) -> Self {
+        LocalSink {
+            cbs: self.cbs.clone(),
+        }
+    }
+}
+
+/// The source object of a LocalStream.
+#[derive(Debug, Clone)]
+enum Source {
+    /// No source.
+    None,
+    /// The source is a type-erased object. Usually a stream of a different type.
+    Erased(#[allow(dead_code)] Rc<dyn Any>),
+}
+
+impl Source {
+    fn stream<T: 'static>(s: &LocalStream<T>) -> Self {
+        Source::Erased(Rc::new(s.clone()))
+    }
+
+    fn stream2<A: 'static, B: 'static>(s1: &LocalStream<A>, s2: &LocalStream<B>) -> Self {
+        Source::Erased(Rc::new((s1.clone(), s2.clone())))
+    }
+}
+
+/// A stream of discrete events sent over time.
+///
+/// This is the single-threaded version of `Stream`.
+#[derive(Debug)]
+pub struct LocalStream<T> {
+    cbs: Rc<Callbacks<T>>,
+    source: Source,
+}
+
+impl<T> LocalStream<T> {
+    /// Creates a stream from it's components.
+    #[inline]
+    fn new(cbs: Rc<Callbacks<T>>, source: Source) -> Self {
+        LocalStream { cbs, source }
+    }
+
+    /// Creates a stream that never fires.
+    #[inline]
+    pub fn never() -> Self {
+        LocalStream::new(Default::default(), Source::None)
+    }
+
+    /// Reads the values from the stream.
+    ///
+    /// This works like `Stream::observe`.
+    pub fn observe<F, R>(&self, f: F)
+    where
+        F: Fn(MaybeOwned<'_, T>) -> R + 'static,
+        R: ObserveResult,
+    {
+        self.cbs.push(move |arg| f(arg).is_callback_alive());
+    }
+
+    /// Observes the stream while keeping a reference to it.
+    ///
+    /// This works like `Stream::observe_strong`, so it will leak memory if the closure never
+    /// unregisters itself.
+    pub fn observe_strong<F, R>(&self, f: F)
+    where
+        F: Fn(MaybeOwned<'_, T>) -> R + 'static,
+        T: 'static,
+        R: ObserveResult,
+    {
+        let this = self.clone();
+        self.cbs.push(move |arg| {
+            let _keepalive = &this;
+            f(arg).is_callback_alive()
+        });
+    }
+
+    /// Chainable version of `LocalStream::observe`.
+    #[inline]
+    pub fn inspect<F, R>(self, f: F) -> Self
+    where
+        F: Fn(MaybeOwned<'_, T>) -> R + 'static,
+        R: ObserveResult,
+    {
+        self.observe(f);
+        self
+    }
+}
+
+impl<T: 'static> LocalStream<T> {
+    /// Maps this stream into another stream using the provided function.
+    #[inline]
+    pub fn map<F, R>(&self, f: F) -> LocalStream<R>
+    where
+        F: Fn(MaybeOwned<'_, T>) -> R + 'static,
+        R: 'static,
+    {
+        self.filter_map(move |arg| Some(f(arg)))
+    }
+
+    /// Creates a new stream that only contains the values where the predicate is `true`.
+    pub fn filter<F>(&self, pred: F) -> Self
+    where
+        F: Fn(&T) -> bool + 'static,
+    {
+        let (new_cbs, weak) = rc_and_weak(Callbacks::new());
+        self.cbs.push(move |arg| {
+            with_weak!(weak, |cb| if pred(&arg) {
+                cb.call(arg)
+            })
+        });
+        LocalStream::new(new_cbs, Source::stream(self))
+    }
+
+    /// Does filter and map on a stream simultaneously.
+    pub fn filter_map<F, R>(&self, f: F) -> LocalStream<R>
+    where
+        F: Fn(MaybeOwned<'_, T>) -> Option<R> + 'static,
+        R: 'static,
+    {
+        let (new_cbs, weak) = rc_and_weak(Callbacks::new());
+        self.cbs.push(move |arg| {
+            with_weak!(weak, |cb| if let Some(val) = f(arg) {
+                cb.call(val)
+            })
+        });
+        LocalStream::new(new_cbs, Source::stream(self))
+    }
+
+    /// Creates a new stream that fires with the events from both streams.
+    pub fn merge(&self, other: &LocalStream<T>) -> Self {
+        let (new_cbs, weak1) = rc_and_weak(Callbacks::new());
+        let weak2 = weak1.clone();
+        self.cbs
+            .push(move |arg| with_weak!(weak1, |cb| cb.call(arg)));
+        other
+            .cbs
+            .push(move |arg| with_weak!(weak2, |cb| cb.call(arg)));
+        LocalStream::new(new_cbs, Source::stream2(self, other))
+    }
+
+    /// Merges two streams of different types using two functions.
+    pub fn merge_with<U, F1, F2, R>(&self, other: &LocalStream<U>, f1: F1, f2: F2) -> LocalStream<R>
+    where
+        F1: Fn(MaybeOwned<'_, T>) -> R + 'static,
+        F2: Fn(MaybeOwned<'_, U>) -> R + 'static,
+        U: 'static,
+        R: 'static,
+    {
+        let (new_cbs, weak1) = rc_and_weak(Callbacks::new());
+        let weak2 = weak1.clone();
+        self.cbs
+            .push(move |arg| with_weak!(weak1, |cb| cb.call(f1(arg))));
+        other
+            .cbs
+            .push(move |arg| with_weak!(weak2, |cb| cb.call(f2(arg))));
+        LocalStream::new(new_cbs, Source::stream2(self, other))
+    }
+
+    /// Merges two streams of different types using a single function that takes an `Either` argument.
+    #[cfg(feature = "either")]
+    #[inline]
+    pub fn merge_with_either<U, F, R>(&self, other: &LocalStream<U>, f: F) -> LocalStream<R>
+    where
+        F: Fn(Either<MaybeOwned<'_, T>, MaybeOwned<'_, U>>) -> R + 'static,
+        U: 'static,
+        R: 'static,
+    {
+        let f = Rc::new(f);
+        let f_ = f.clone();
+        self.merge_with(
+            other,
+            move |a| f(Either::Left(a)),
+            move |b| f_(Either::Right(b)),
+        )
+    }
+
+    /// Accumulates the values sent over this stream.
+    ///
+    /// This works like `Stream::fold`, so a panic inside the closure will leave the storage empty.
+    pub fn fold<A, F>(&self, initial: A, f: F) -> LocalSignal<A>
+    where
+        F: Fn(A, MaybeOwned<'_, T>) -> A + 'static,
+        A: Clone + 'static,
+    {
+        let (storage, weak) = rc_and_weak(Storage::new(initial));
+        self.cbs.push(move |arg| {
+            with_weak!(weak, |st| {
+                st.replace(|old| f(old, arg));
+            })
+        });
+        LocalSignal::from_storage(storage, self.clone())
+    }
+
+    /// Folds the stream by cloning the accumulator.
+    pub fn fold_clone<A, F>(&self, initial: A, f: F) -> LocalSignal<A>
+    where
+        F: Fn(A, MaybeOwned<'_, T>) -> A + 'static,
+        A: Clone + 'static,
+    {
+        let (storage, weak) = rc_and_weak(Storage::new(initial));
+        self.cbs.push(move |arg| {
+            with_weak!(weak, |st| {
+                st.replace_clone(|old| f(old, arg));
+            })
+        });
+        LocalSignal::from_storage(storage, self.clone())
+    }
+
+    /// Maps each stream event to `0..N` output values.
+    pub fn map_n<F, R>(&self, f: F) -> LocalStream<R>
+    where
+        F: Fn(MaybeOwned<'_, T>, LocalSender<R>) + 'static,
+        R: 'static,
+    {
+        let (new_cbs, weak) = rc_and_weak(Callbacks::new());
+        self.cbs
+            .push(move |arg| with_weak!(weak, |cb| f(arg, LocalSender::new(cb))));
+        LocalStream::new(new_cbs, Source::stream(self))
+    }
+
+    /// Folds the stream and returns the accumulator values as a stream.
+    pub fn scan<A, F>(&self, initial: A, f: F) -> LocalStream<A>
+    where
+        F: Fn(A, MaybeOwned<'_, T>) -> A + 'static,
+        A: Clone + 'static,
+    {
+        let (new_cbs, weak) = rc_and_weak(Callbacks::new());
+        let storage = Storage::new(initial);
+        self.cbs.push(move |arg| {
+            with_weak!(weak, |cb| {
+                let new = storage.replace_fetch(|old| f(old, arg));
+                cb.call(new)
+            })
+        });
+        LocalStream::new(new_cbs, Source::stream(self))
+    }
+
+    /// Folds the stream and returns `0..N` output values.
+    pub fn scan_n<A, F, R>(&self, initial: A, f: F) -> LocalStream<R>
+    where
+        F: Fn(A, MaybeOwned<'_, T>, LocalSender<R>) -> A + 'static,
+        A: 'static,
+        R: 'static,
+    {
+        let (new_cbs, weak) = rc_and_weak(Callbacks::new());
+        let storage = Storage::new(initial);
+        self.cbs.push(move |arg| {
+            with_weak!(weak, |cb| storage.replace(|old| f(
+                old,
+                arg,
+                LocalSender::new(cb)
+            )))
+        });
+        LocalStream::new(new_cbs, Source::stream(self))
+    }
+
+    /// Creates a collection from the values sent to this stream.
+    #[inline]
+    pub fn collect<C>(&self) -> LocalSignal<C>
+    where
+        C: Default + Extend<T> + Clone + 'static,
+        T: Clone,
+    {
+        self.fold(C::default(), |mut a, v| {
+            a.extend(Some(v.into_owned()));
+            a
+        })
+    }
+
+    /// Returns a stream that contains only the Nth value from the input stream.
+    pub fn element_at(&self, index: usize) -> Self {
+        let (new_cbs, weak) = rc_and_weak(Callbacks::new());
+        let pos = Cell::new(0);
+        self.cbs.push(move |arg| {
+            weak.upgrade().is_some_and(|cb| {
+                let cur_pos = pos.get();
+                pos.set(cur_pos + 1);
+                if cur_pos == index {
+                    cb.call(arg);
+                }
+                cur_pos < index // drop the callback after we're done
+            })
+        });
+        LocalStream::new(new_cbs, Source::stream(self))
+    }
+
+    /// Returns a stream that contains the values with index in the specified range.
+    pub fn elements_between<B>(&self, range: B) -> Self
+    where
+        B: RangeBounds<usize> + 'static,
+    {
+        let (new_cbs, weak) = rc_and_weak(Callbacks::new());
+        let pos = Cell::new(0);
+        self.cbs.push(move |arg| {
+            weak.upgrade().is_some_and(|cb| {
+                let cur_pos = pos.get();
+                pos.set(cur_pos + 1);
+                let after_start = match range.start_bound() {
+                    Bound::Included(s) => cur_pos >= *s,
+                    Bound::Excluded(s) => cur_pos > *s,
+                    Bound::Unbounded => true,
+                };
+                let before_end = match range.end_bound() {
+                    Bound::Included(e) => cur_pos <= *e,
+                    Bound::Excluded(e) => cur_pos < *e,
+                    Bound::Unbounded => true,
+                };
+                if after_start && before_end {
+                    cb.call(arg)
+                }
+                before_end // drop the callback after we're past the end
+            })
+        });
+        LocalStream::new(new_cbs, Source::stream(self))
+    }
+}
+
+impl<T: Clone + 'static> LocalStream<T> {
+    /// Creates a Signal that holds the last value sent to this stream.
+    #[inline]
+    pub fn hold(&self, initial: T) -> LocalSignal<T> {
+        self.hold_if(initial, |_| true)
+    }
+
+    /// Holds the last value in this stream where the predicate is `true`.
+    pub fn hold_if<F>(&self, initial: T, pred: F) -> LocalSignal<T>
+    where
+        F: Fn(&T) -> bool + 'static,
+    {
+        let (storage, weak) = rc_and_weak(Storage::new(initial));
+        self.cbs.push(move |arg| {
+            with_weak!(weak, |st| if pred(&arg) {
+                st.set(arg.into_owned());
+            })
+        });
+        LocalSignal::from_storage(storage, self.clone())
+    }
+
+    /// Collects all pairs of values from two streams.
+    #[inline]
+    pub fn zip<U>(&self, other: &LocalStream<U>) -> LocalStream<(T, U)>
+    where
+        U: Clone + 'static,
+    {
+        self.zip_with(other, |a, b| (a, b))
+    }
+
+    /// Zips two streams using a custom function.
+    pub fn zip_with<U, F, R>(&self, other: &LocalStream<U>, f: F) -> LocalStream<R>
+    where
+        F: Fn(T, U) -> R + 'static,
+        U: Clone + 'static,
+        R: 'static,
+    {
+        let (new_cbs, weak1) = rc_and_weak(Callbacks::new());
+        let weak2 = weak1.clone();
+
+        let left = Rc::new(RefCell::new(VecDeque::new()));
+        let right = Rc::new(RefCell::new(VecDeque::new()));
+        let left1 = left.clone();
+        let right1 = right.clone();
+        let f = Rc::new(f);
+        let f_ = f.clone();
+
+        self.cbs.push(move |arg| {
+            with_weak!(weak1, |cb| {
+                let popped = right1.borrow_mut().pop_front();
+                if let Some(val) = popped {
+                    cb.call(f(arg.into_owned(), val));
+                } else {
+                    left.borrow_mut().push_back(arg.into_owned());
+                }
+            })
+        });
+
+        other.cbs.push(move |arg| {
+            with_weak!(weak2, |cb| {
+                let popped = left1.borrow_mut().pop_front();
+                if let Some(val) = popped {
+                    cb.call(f_(val, arg.into_owned()));
+                } else {
+                    right.borrow_mut().push_back(arg.into_owned());
+                }
+            })
+        });
+
+        LocalStream::new(new_cbs, Source::stream2(self, other))
+    }
+
+    /// Collects pairs of values from two streams using their last value seen.
+    #[inline]
+    pub fn combine<U>(&self, other: &LocalStream<U>) -> LocalStream<(T, U)>
+    where
+        U: Clone + 'static,
+    {
+        self.combine_with(other, |a, b| (a, b))
+    }
+
+    /// Combines two streams using a custom function.
+    pub fn combine_with<U, F, R>(&self, other: &LocalStream<U>, f: F) -> LocalStream<R>
+    where
+        F: Fn(T, U) -> R + 'static,
+        U: Clone + 'static,
+        R: 'static,
+    {
+        let (new_cbs, weak1) = rc_and_weak(Callbacks::new());
+        let weak2 = weak1.clone();
+
+        let left = Rc::new(RefCell::new(None));
+        let right = Rc::new(RefCell::new(None));
+        let left1 = left.clone();
+        let right1 = right.clone();
+        let f = Rc::new(f);
+        let f_ = f.clone();
+
+        self.cbs.push(move |arg| {
+            with_weak!(weak1, |cb| {
+                let arg = arg.into_owned();
+                *left.borrow_mut() = Some(arg.clone());
+                let other = right1.borrow().clone();
+                if let Some(val) = other {
+                    cb.call(f(arg, val));
+                }
+            })
+        });
+
+        other.cbs.push(move |arg| {
+            with_weak!(weak2, |cb| {
+                let arg = arg.into_owned();
+                *right.borrow_mut() = Some(arg.clone());
+                let other = left1.borrow().clone();
+                if let Some(val) = other {
+                    cb.call(f_(val, arg));
+                }
+            })
+        });
+
+        LocalStream::new(new_cbs, Source::stream2(self, other))
+    }
+
+    /// Creates a future that returns the next value sent to this stream.
+    #[inline]
+    pub fn next(&self) -> LocalStreamFuture<T> {
+        LocalStreamFuture::new(self.clone())
+    }
+
+    /// Creates a thread-safe `Stream` that receives the events sent to this stream.
+    ///
+    /// The events are sent to the returned stream from the thread that owns this stream. The
+    /// returned stream doesn't keep this stream alive, so the event chain that feeds it must be
+    /// kept by the owner thread.
+    pub fn to_stream(&self) -> Stream<T> {
+        let sink = Sink::new();
+        let stream = sink.stream();
+        self.observe(move |arg| sink.send(arg));
+        stream
+    }
+}
+
+impl<T: Clone + 'static> LocalStream<Option<T>> {
+    /// Filters a stream of `Option`, returning only the unwrapped `Some` values.
+    #[inline]
+    pub fn filter_some(&self) -> LocalStream<T> {
+        self.filter_first()
+    }
+}
+
+impl<T: Clone + 'static, E: Clone + 'static> LocalStream<Result<T, E>> {
+    /// Filters a stream of `Result`, returning only the unwrapped `Ok` values.
+    #[inline]
+    pub fn filter_ok(&self) -> LocalStream<T> {
+        self.filter_first()
+    }
+
+    /// Filters a stream of `Result`, returning only the unwrapped `Err` values.
+    #[inline]
+    pub fn filter_err(&self) -> LocalStream<E> {
+        self.filter_second()
+    }
+}
+
+impl<T: SumType2 + Clone + 'static> LocalStream<T>
+where
+    T::Type1: 'static,
+    T::Type2: 'static,
+{
+    /// Creates a stream with only the first element of a sum type.
+    pub fn filter_first(&self) -> LocalStream<T::Type1> {
+        self.filter_map(|res| {
+            if res.is_type1() {
+                res.into_owned().into_type1()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Creates a stream with only the second element of a sum type.
+    pub fn filter_second(&self) -> LocalStream<T::Type2> {
+        self.filter_map(|res| {
+            if res.is_type2() {
+                res.into_owned().into_type2()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Splits a two element sum type stream into two streams with the unwrapped values.
+    pub fn split(&self) -> (LocalStream<T::Type1>, LocalStream<T::Type2>) {
+        let (cbs_1, weak_1) = rc_and_weak(Callbacks::new());
+        let (cbs_2, weak_2) = rc_and_weak(Callbacks::new());
+        self.cbs.push(move |result| {
+            if result.is_type1() {
+                if let Some(cb) = weak_1.upgrade() {
+                    cb.call(result.into_owned().into_type1().unwrap());
+                    true
+                } else {
+                    // drop callback if both output streams dropped
+                    weak_2.upgrade().is_some()
+                }
+            } else {
+                // ..if result.is_type2()
+                if let Some(cb) = weak_2.upgrade() {
+                    cb.call(result.into_owned().into_type2().unwrap());
+                    true
+                } else {
+                    weak_1.upgrade().is_some()
+                }
+            }
+        });
+        let source = Source::stream(self);
+        let stream_1 = LocalStream::new(cbs_1, source.clone());
+        let stream_2 = LocalStream::new(cbs_2, source);
+        (stream_1, stream_2)
+    }
+}
+
+impl<T: 'static> LocalStream<LocalStream<T>> {
+    /// Listens to the events from the last stream sent to a nested stream.
+    pub fn switch(&self) -> LocalStream<T> {
+        let (new_cbs, weak) = rc_and_weak(Callbacks::new());
+        let id = Rc::new(Cell::new(0)); // id of each stream sent
+        self.cbs.push(move |stream| {
+            if weak.upgrade().is_none() {
+                return false;
+            }
+            let cbs_w = weak.clone();
+            let cur_id = id.clone();
+            // increment the id so it will only send to the last stream
+            let my_id = id.get() + 1;
+            id.set(my_id);
+            // redirect the inner stream to the output stream
+            stream.cbs.push(move |arg| {
+                if my_id != cur_id.get() {
+                    return false;
+                }
+                with_weak!(cbs_w, |cb| cb.call(arg))
+            });
+            true
+        });
+        LocalStream::new(new_cbs, Source::stream(self))
+    }
+}
+
+impl<T> Clone for LocalStream<T> {
+    /// Creates a copy of this stream that references the same event chain.
+    #[inline]
+    fn clone(&self) -> Self {
+        LocalStream {
+            cbs: self.cbs.clone(),
+            source: self.source.clone(),
+        }
+    }
+}
+
+impl<T> Default for LocalStream<T> {
+    /// Creates a stream that never fires.
+    #[inline]
+    fn default() -> Self {
+        LocalStream::never()
+    }
+}
+
+/// Sends values into a local stream.
+///
+/// This is a restricted version of `LocalSink` used by `LocalStream::map_n` and
+/// `LocalStream::scan_n`.
+#[derive(Debug)]
+pub struct LocalSender<T>(LocalSink<T>);
+
+impl<T> LocalSender<T> {
+    /// Constructs a new LocalSender from a list of callbacks.
+    #[inline]
+    fn new(cbs: Rc<Callbacks<T>>) -> Self {
+        LocalSender(LocalSink { cbs })
+    }
+
+    /// Sends a value.
+    #[inline]
+    pub fn send(&self, val: T) {
+        self.0.send(val)
+    }
+
+    /// Sends multiple values.
+    #[inline]
+    pub fn feed(&self, iter: impl IntoIterator<Item = T>) {
+        self.0.feed(iter)
+    }
+}
+
+impl<T> Clone for LocalSender<T> {
+    /// Creates a copy of this sender that references the same event source.
+    #[inline]
+    fn clone(&self) -> Self {
+        LocalSender(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::needless_borrows_for_generic_args)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_stream_basic() {
+        let sink = LocalSink::new();
+        let result = sink.stream().collect::<Vec<_>>();
+
+        sink.send(42);
+        sink.feed(0..3);
+        sink.feed(&[11, 22]);
+
+        assert_eq!(result.sample(), [42, 0, 1, 2, 11, 22]);
+    }
+
+    #[test]
+    fn local_stream_non_send() {
+        // `Rc` can't be captured by the thread-safe streams
+        let counter = Rc::new(Cell::new(0));
+        let counter_ = counter.clone();
+        let sink = LocalSink::new();
+        let even = sink.stream().filter(|x| x % 2 == 0);
+        even.observe(move |x| counter_.set(counter_.get() + *x));
+
+        sink.feed(1..=6);
+
+        assert_eq!(counter.get(), 12);
+    }
+
+    #[test]
+    fn local_stream_operations() {
+        let sink1 = LocalSink::new();
+        let sink2 = LocalSink::new();
+        let merged = sink1.stream().merge(&sink2.stream()).collect::<Vec<_>>();
+        let zipped = sink1.stream().zip(&sink2.stream()).collect::<Vec<_>>();
+        let combined = sink1.stream().combine(&sink2.stream()).hold((0, 0));
+        let scanned = sink1.stream().scan(0, |a, n| a + *n).collect::<Vec<_>>();
+        let (pos, neg) = sink2
+            .stream()
+            .map(|x| if *x > 0 { Ok(*x) } else { Err(*x) })
+            .split();
+        let pos = pos.collect::<Vec<_>>();
+        let neg = neg.collect::<Vec<_>>();
+
+        sink1.send(1);
+        sink2.send(-2);
+        sink1.send(3);
+        sink2.send(4);
+
+        assert_eq!(merged.sample(), [1, -2, 3, 4]);
+        assert_eq!(zipped.sample(), [(1, -2), (3, 4)]);
+        assert_eq!(combined.sample(), (3, 4));
+        assert_eq!(scanned.sample(), [1, 4]);
+        assert_eq!(pos.sample(), [4]);
+        assert_eq!(neg.sample(), [-2]);
+    }
+
+    #[test]
+    fn local_stream_switch() {
+        let stream_sink = LocalSink::new();
+        let sink1 = LocalSink::new();
+        let sink2 = LocalSink::new();
+        let switched = stream_sink.stream().switch().collect::<Vec<_>>();
+
+        sink1.send(1);
+        stream_sink.send(sink2.stream());
+        sink1.send(3);
+        sink2.send(4);
+        stream_sink.send(sink1.stream());
+        sink1.send(5);
+        sink2.send(6);
+
+        assert_eq!(switched.sample(), [4, 5]);
+    }
+
+    #[test]
+    fn local_stream_recursive() {
+        let sink = LocalSink::new();
+        let sink_ = sink.clone();
+        let stream = sink.stream();
+        let stream_ = stream.clone();
+        let result = stream.collect::<Vec<_>>();
+        stream.observe(move |x| {
+            if *x > 0 {
+                stream_.observe(|_| false);
+                sink_.send(*x - 1);
+            }
+        });
+
+        sink.send(2);
+
+        assert_eq!(result.sample(), [2, 1, 0]);
+        assert_eq!(sink.cbs.len(), 4);
+
+        sink.send(0);
+        assert_eq!(sink.cbs.len(), 2);
+    }
+
+    #[test]
+    fn local_stream_to_stream() {
+        let sink = LocalSink::new();
+        let local = sink.stream().map(|x| *x * 2);
+        let result = local.to_stream().hold(0);
+
+        sink.send(21);
+
+        assert_eq!(result.sample(), 42);
+    }
+}
@@ -0,0 +1,301 @@
+//! Persistence of signal state.
+//!
+//! Signals created with `Stream::fold_persistent` register their accumulator with a `StateStore`
+//! under an unique key. The store can then take a `Snapshot` of all the registered accumulators,
+//! that can be saved with any self-describing serde format (like JSON), and later restore it.
+//!
+//! Values can be restored before or after the signals are created. If a signal isn't registered
+//! yet when the snapshot is restored, it's value is kept and used as the initial value when the
+//! signal is created. This allows restoring the state before any events start flowing.
+//!
+//! # Example
+//! ```
+//! use frappe::persist::StateStore;
+//! use frappe::Sink;
+//!
+//! let store = StateStore::new();
+//! let sink = Sink::new();
+//! let counter = sink.stream().fold_persistent(&store, "counter", 0, |n, x| n + *x);
+//! sink.feed(1..=3);
+//! let saved = serde_json::to_string(&store.snapshot().unwrap()).unwrap();
+//!
+//! // on the next run of the program
+//! let store = StateStore::new();
+//! store.restore(&serde_json::from_str(&saved).unwrap()).unwrap();
+//! let sink = Sink::new();
+//! let counter = sink.stream().fold_persistent(&store, "counter", 0, |n, x| n + *x);
+//! assert_eq!(counter.sample(), 6);
+//! sink.send(4);
+//! assert_eq!(counter.sample(), 10);
+//! ```
+
+use crate::sync::Mutex;
+use crate::types::Storage;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Weak};
+
+/// The serialized state of a `StateStore`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Snapshot {
+    values: BTreeMap<String, Value>,
+}
+
+impl Snapshot {
+    /// Gets the value stored for `key`.
+    pub fn get<A: DeserializeOwned>(&self, key: &str) -> Option<Result<A, serde_json::Error>> {
+        self.values.get(key).map(A::deserialize)
+    }
+
+    /// Iterates over the stored keys.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(String::as_str)
+    }
+
+    /// Gets the amount of values stored.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Checks if the snapshot is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// Type erased accumulator registered on a store.
+trait Entry: Send + Sync {
+    /// Serializes the current value, or returns `None` if the signal was dropped or it's value is
+    /// missing.
+    fn save(&self) -> Option<Result<Value, serde_json::Error>>;
+
+    /// Parses a value without storing it.
+    fn parse(&self, value: &Value) -> Result<Box<dyn Any>, serde_json::Error>;
+
+    /// Stores a value returned by `parse`.
+    fn apply(&self, value: Box<dyn Any>);
+
+    /// Checks if the signal is still alive.
+    fn is_alive(&self) -> bool;
+}
+
+impl<A> Entry for Weak<Storage<A>>
+where
+    A: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    fn save(&self) -> Option<Result<Value, serde_json::Error>> {
+        let val = self.upgrade()?.try_get().ok()?;
+        Some(serde_json::to_value(val))
+    }
+
+    fn parse(&self, value: &Value) -> Result<Box<dyn Any>, serde_json::Error> {
+        A::deserialize(value).map(|val| Box::new(val) as Box<dyn Any>)
+    }
+
+    fn apply(&self, value: Box<dyn Any>) {
+        if let (Some(st), Ok(val)) = (self.upgrade(), value.downcast::<A>()) {
+            st.set(*val);
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        self.strong_count() > 0
+    }
+}
+
+#[derive(Default)]
+struct StoreInner {
+    entries: BTreeMap<String, Box<dyn Entry>>,
+    /// Restored values waiting for their signal to be created.
+    pending: BTreeMap<String, Value>,
+}
+
+/// A registry of persistent signal accumulators.
+///
+/// The store only keeps weak references to the signals, so it doesn't keep them alive.
+#[derive(Default)]
+pub struct StateStore {
+    inner: Mutex<StoreInner>,
+}
+
+impl StateStore {
+    /// Creates an empty store.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Registers an accumulator under `key`, replacing any previous one with the same key.
+    ///
+    /// If a value was restored for this key, it's stored on the accumulator. A value that can't
+    /// be deserialized is discarded, and the accumulator keeps it's initial value.
+    pub(crate) fn register<A>(&self, key: &str, storage: &Arc<Storage<A>>)
+    where
+        A: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    {
+        let entry = Arc::downgrade(storage);
+        let mut inner = self.inner.lock();
+        if let Some(val) = inner.pending.remove(key) {
+            if let Ok(val) = entry.parse(&val) {
+                entry.apply(val);
+            }
+        }
+        inner.entries.retain(|_, e| e.is_alive());
+        inner.entries.insert(key.to_string(), Box::new(entry));
+    }
+
+    /// Takes a snapshot of the current value of all the registered signals.
+    ///
+    /// The values restored for signals that weren't created yet are also included. Signals that
+    /// lost their value (because a fold closure panicked) are skipped.
+    pub fn snapshot(&self) -> Result<Snapshot, serde_json::Error> {
+        let inner = self.inner.lock();
+        let mut values = inner.pending.clone();
+        for (key, entry) in &inner.entries {
+            if let Some(val) = entry.save() {
+                values.insert(key.clone(), val?);
+            }
+        }
+        Ok(Snapshot { values })
+    }
+
+    /// Restores the values from a snapshot.
+    ///
+    /// The registered signals are updated immediately. Values for signals that aren't registered
+    /// are kept until a signal with the same key is registered. If any of the values can't be
+    /// deserialized into the signal's type, nothing is restored.
+    pub fn restore(&self, snapshot: &Snapshot) -> Result<(), serde_json::Error> {
+        let mut inner = self.inner.lock();
+        let mut parsed = Vec::new();
+        for (key, val) in &snapshot.values {
+            match inner.entries.get(key).filter(|e| e.is_alive()) {
+                Some(entry) => parsed.push((key, Some(entry.parse(val)?))),
+                None => parsed.push((key, None)),
+            }
+        }
+        for (key, val) in parsed {
+            match val {
+                Some(val) => inner.entries[key].apply(val),
+                None => {
+                    inner
+                        .pending
+                        .insert(key.clone(), snapshot.values[key].clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Gets the keys of the signals currently registered.
+    pub fn keys(&self) -> Vec<String> {
+        let inner = self.inner.lock();
+        inner
+            .entries
+            .iter()
+            .filter(|(_, e)| e.is_alive())
+            .map(|(k, _)| k.clone())
+            .collect()
+    }
+}
+
+impl fmt::Debug for StateStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let inner = self.inner.lock();
+        f.debug_struct("StateStore")
+            .field("entries", &inner.entries.keys().collect::<Vec<_>>())
+            .field("pending", &inner.pending.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sink;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Todo {
+        text: String,
+        done: bool,
+    }
+
+    #[test]
+    fn persist_snapshot() {
+        let store = StateStore::new();
+        let sink = Sink::new();
+        let count = sink
+            .stream()
+            .fold_persistent(&store, "count", 0, |n, _| n + 1);
+        let todos = sink
+            .stream()
+            .fold_persistent(&store, "todos", vec![], |mut v, s| {
+                v.push(Todo {
+                    text: String::clone(&s),
+                    done: false,
+                });
+                v
+            });
+        sink.send("buy milk".to_string());
+        sink.send("walk dog".to_string());
+
+        let snapshot = store.snapshot().unwrap();
+        assert_eq!(snapshot.keys().collect::<Vec<_>>(), ["count", "todos"]);
+        assert_eq!(snapshot.get::<i32>("count").unwrap().unwrap(), 2);
+        assert_eq!(
+            serde_json::to_string(&snapshot).unwrap(),
+            r#"{"count":2,"todos":[{"done":false,"text":"buy milk"},{"done":false,"text":"walk dog"}]}"#
+        );
+
+        sink.send("feed cat".to_string());
+        assert_eq!(count.sample(), 3);
+        store.restore(&snapshot).unwrap();
+        assert_eq!(count.sample(), 2);
+        assert_eq!(todos.sample().len(), 2);
+
+        drop(count);
+        assert_eq!(store.keys(), ["todos"]);
+        assert_eq!(store.snapshot().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn persist_restore_before() {
+        let snapshot: Snapshot = serde_json::from_str(r#"{"a":10,"b":"hello"}"#).unwrap();
+        let store = StateStore::new();
+        store.restore(&snapshot).unwrap();
+
+        let sink = Sink::new();
+        let a = sink.stream().fold_persistent(&store, "a", 0, |a, x| a + *x);
+        // the type doesn't match, so it's discarded
+        let b = sink.stream().fold_persistent(&store, "b", 0, |a, x| a * *x);
+        assert_eq!(a.sample(), 10);
+        assert_eq!(b.sample(), 0);
+
+        sink.send(5);
+        assert_eq!(a.sample(), 15);
+        assert_eq!(
+            store.snapshot().unwrap(),
+            serde_json::from_str(r#"{"a":15,"b":0}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn persist_restore_invalid() {
+        let store = StateStore::new();
+        let sink = Sink::<i32>::new();
+        let a = sink.stream().fold_persistent(&store, "a", 1, |a, x| a + *x);
+        let b = sink.stream().fold_persistent(&store, "b", 2, |a, x| a + *x);
+
+        let snapshot = serde_json::from_str(r#"{"a":10,"b":"invalid","c":3}"#).unwrap();
+        assert!(store.restore(&snapshot).is_err());
+        assert_eq!(a.sample(), 1);
+        assert_eq!(b.sample(), 2);
+        assert_eq!(store.snapshot().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,403 @@
+//! Elm-style application architecture.
+//!
+//! A `Program` holds the application state (the model) and changes it in response to messages.
+//! Every message is passed to an `update` function together with the current model, and it
+//! returns the new model and a `Cmd` with side effects to run. Commands are run by an `Executor`,
+//! and can send new messages back into the program using a `Sender`.
+//!
+//! The view is built by mapping the model signal, or by observing the stream of model changes.
+//!
+//! # Example
+//! ```
+//! use frappe::program::{Cmd, Program};
+//!
+//! #[derive(Clone)]
+//! enum Msg {
+//!     Increment,
+//!     Reset,
+//!     Loaded(i32),
+//! }
+//!
+//! let program = Program::new(0, |count, msg| match msg {
+//!     Msg::Increment => (count + 1, Cmd::none()),
+//!     // pretend we're loading the value from somewhere
+//!     Msg::Reset => (count, Cmd::perform(|sender| sender.send(Msg::Loaded(100)))),
+//!     Msg::Loaded(n) => (n, Cmd::none()),
+//! });
+//! let view = program.view(|count| format!("count: {}", count));
+//!
+//! program.dispatch(Msg::Increment);
+//! assert_eq!(view.sample(), "count: 1");
+//! program.dispatch(Msg::Reset);
+//! assert_eq!(view.sample(), "count: 100");
+//! ```
+
+use crate::signal::Signal;
+use crate::stream::{Sink, Stream};
+use crate::sync::Mutex;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// A side effect that is ready to run.
+pub type Task = Box<dyn FnOnce() + Send>;
+
+/// Runs the side effects of commands.
+///
+/// It's implemented for any `Fn(Task)`, so a closure can be used as an executor.
+pub trait Executor: Send + Sync {
+    /// Runs a task.
+    fn execute(&self, task: Task);
+}
+
+impl<F: Fn(Task) + Send + Sync> Executor for F {
+    #[inline]
+    fn execute(&self, task: Task) {
+        self(task)
+    }
+}
+
+/// Executor that runs the tasks immediately on the current thread.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Inline;
+
+impl Executor for Inline {
+    #[inline]
+    fn execute(&self, task: Task) {
+        task()
+    }
+}
+
+/// Executor that runs every task on a new thread.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Threaded;
+
+#[cfg(feature = "std")]
+impl Executor for Threaded {
+    fn execute(&self, task: Task) {
+        std::thread::spawn(task);
+    }
+}
+
+/// Sends messages into a program.
+pub struct Sender<Msg>(Arc<dyn Fn(Msg) + Send + Sync>);
+
+impl<Msg> Sender<Msg> {
+    /// Sends a message.
+    #[inline]
+    pub fn send(&self, msg: Msg) {
+        (self.0)(msg)
+    }
+
+    /// Creates a sender that transforms the messages before sending them here.
+    pub fn map<T, F>(&self, f: F) -> Sender<T>
+    where
+        F: Fn(T) -> Msg + Send + Sync + 'static,
+        Msg: 'static,
+    {
+        let this = self.clone();
+        Sender(Arc::new(move |msg| this.send(f(msg))))
+    }
+}
+
+impl<Msg> Clone for Sender<Msg> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Sender(self.0.clone())
+    }
+}
+
+impl<Msg> fmt::Debug for Sender<Msg> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Sender")
+    }
+}
+
+type Effect<Msg> = Box<dyn FnOnce(&Sender<Msg>) + Send>;
+
+/// Side effects returned by the update function.
+pub struct Cmd<Msg> {
+    effects: Vec<Effect<Msg>>,
+}
+
+impl<Msg: 'static> Cmd<Msg> {
+    /// Creates a command that does nothing.
+    #[inline]
+    pub fn none() -> Self {
+        Cmd {
+            effects: Vec::new(),
+        }
+    }
+
+    /// Creates a command that sends a message.
+    pub fn msg(msg: Msg) -> Self
+    where
+        Msg: Send,
+    {
+        Self::perform(move |sender| sender.send(msg))
+    }
+
+    /// Creates a command that runs a side effect.
+    ///
+    /// The closure can use the sender to report it's results back to the program.
+    pub fn perform<F>(f: F) -> Self
+    where
+        F: FnOnce(&Sender<Msg>) + Send + 'static,
+    {
+        Cmd {
+            effects: alloc::vec![Box::new(f)],
+        }
+    }
+
+    /// Combines multiple commands into one.
+    pub fn batch(cmds: impl IntoIterator<Item = Self>) -> Self {
+        Cmd {
+            effects: cmds.into_iter().flat_map(|cmd| cmd.effects).collect(),
+        }
+    }
+
+    /// Transforms the messages sent by this command.
+    ///
+    /// This allows using the commands of a child component with a message type that wraps the
+    /// child messages.
+    pub fn map<R, F>(self, f: F) -> Cmd<R>
+    where
+        F: Fn(Msg) -> R + Send + Sync + 'static,
+        R: 'static,
+    {
+        let f = Arc::new(f);
+        let effects = self.effects.into_iter().map(|effect| {
+            let f = f.clone();
+            Box::new(move |sender: &Sender<R>| effect(&sender.map(move |msg| f(msg)))) as Effect<R>
+        });
+        Cmd {
+            effects: effects.collect(),
+        }
+    }
+
+    /// Checks if the command does nothing.
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        self.effects.is_empty()
+    }
+}
+
+impl<Msg: 'static> Default for Cmd<Msg> {
+    /// Creates a command that does nothing.
+    #[inline]
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl<Msg> fmt::Debug for Cmd<Msg> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Cmd")
+            .field("effects", &self.effects.len())
+            .finish()
+    }
+}
+
+/// An application driven by messages.
+pub struct Program<Model, Msg> {
+    sink: Sink<Msg>,
+    sender: Sender<Msg>,
+    model: Signal<Model>,
+    changes: Sink<Model>,
+}
+
+impl<Model, Msg> Program<Model, Msg>
+where
+    Model: Clone + Send + Sync + 'static,
+    Msg: Clone + Send + Sync + 'static,
+{
+    /// Creates a program that runs the commands on the current thread.
+    pub fn new<U>(initial: Model, update: U) -> Self
+    where
+        U: Fn(Model, Msg) -> (Model, Cmd<Msg>) + Send + Sync + 'static,
+    {
+        Self::with_executor(initial, update, Inline)
+    }
+
+    /// Creates a program that runs the commands with an executor.
+    pub fn with_executor<U, E>(initial: Model, update: U, executor: E) -> Self
+    where
+        U: Fn(Model, Msg) -> (Model, Cmd<Msg>) + Send + Sync + 'static,
+        E: Executor + 'static,
+    {
+        let sink = Sink::<Msg>::new();
+        let changes = Sink::new();
+        // the results of each update, in the same order they were applied to the model
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+
+        let q = queue.clone();
+        let model = sink.stream().fold(initial, move |model, msg| {
+            let (model, cmd) = update(model, msg.into_owned());
+            q.lock().push_back((model.clone(), cmd));
+            model
+        });
+
+        let s = sink.clone();
+        let sender = Sender(Arc::new(move |msg| s.send(msg)));
+        // a strong reference would keep the sink alive from it's own callback
+        let weak = Arc::downgrade(&sender.0);
+        let changes_ = changes.clone();
+        sink.stream().observe(move |_| {
+            let next = queue.lock().pop_front();
+            if let Some((model, cmd)) = next {
+                changes_.send(model);
+                for effect in cmd.effects {
+                    if let Some(sender) = weak.upgrade() {
+                        let sender = Sender(sender);
+                        executor.execute(Box::new(move || effect(&sender)));
+                    }
+                }
+            }
+        });
+
+        Program {
+            sink,
+            sender,
+            model,
+            changes,
+        }
+    }
+
+    /// Sends a message into the program.
+    #[inline]
+    pub fn dispatch(&self, msg: Msg) {
+        self.sink.send(msg)
+    }
+
+    /// Gets a sender for messages.
+    #[inline]
+    pub fn sender(&self) -> Sender<Msg> {
+        self.sender.clone()
+    }
+
+    /// Gets a signal with the current model.
+    #[inline]
+    pub fn model(&self) -> Signal<Model> {
+        self.model.clone()
+    }
+
+    /// Gets a stream that receives the model every time it's updated.
+    #[inline]
+    pub fn changes(&self) -> Stream<Model> {
+        self.changes.stream()
+    }
+
+    /// Creates a signal that renders the current model.
+    pub fn view<F, R>(&self, f: F) -> Signal<R>
+    where
+        F: Fn(Model) -> R + Send + Sync + 'static,
+    {
+        self.model.map(f)
+    }
+}
+
+impl<Model, Msg> Clone for Program<Model, Msg> {
+    /// Creates a copy of this object that shares the same model.
+    fn clone(&self) -> Self {
+        Program {
+            sink: self.sink.clone(),
+            sender: self.sender.clone(),
+            model: self.model.clone(),
+            changes: self.changes.clone(),
+        }
+    }
+}
+
+impl<Model: fmt::Debug, Msg: fmt::Debug> fmt::Debug for Program<Model, Msg> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Program")
+            .field("sink", &self.sink)
+            .field("model", &self.model)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::String;
+    #[cfg(feature = "std")]
+    use futures::executor::block_on;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Msg {
+        Add(i32),
+        Double,
+        Log(String),
+    }
+
+    fn update(model: (i32, Vec<String>), msg: Msg) -> ((i32, Vec<String>), Cmd<Msg>) {
+        let (n, mut log) = model;
+        match msg {
+            Msg::Add(x) => ((n + x, log), Cmd::none()),
+            Msg::Double => (
+                (n * 2, log),
+                Cmd::batch(vec![
+                    Cmd::msg(Msg::Add(1)),
+                    Cmd::msg(Msg::Log("doubled".into())),
+                ]),
+            ),
+            Msg::Log(s) => {
+                log.push(s);
+                ((n, log), Cmd::none())
+            }
+        }
+    }
+
+    #[test]
+    fn program_update() {
+        let program = Program::new((0, vec![]), update);
+        let changes = program.changes().map(|m| m.0).collect::<Vec<_>>();
+        let view = program.view(|(n, log)| alloc::format!("{} {:?}", n, log));
+
+        program.dispatch(Msg::Add(2));
+        program.sender().send(Msg::Double);
+        assert_eq!(program.model().sample().0, 5);
+        assert_eq!(view.sample(), r#"5 ["doubled"]"#);
+        assert_eq!(changes.sample(), [2, 4, 5, 5]);
+    }
+
+    #[test]
+    fn program_cmd_map() {
+        // the child component only knows about numbers
+        let child = |n: i32| Cmd::msg(n * 2);
+        assert!(!child(1).is_none());
+        assert!(Cmd::<Msg>::default().is_none());
+
+        let program = Program::new(0, move |n, msg| match msg {
+            Msg::Add(x) => (n + x, Cmd::none()),
+            Msg::Double => (n, child(n).map(Msg::Add)),
+            Msg::Log(_) => (n, Cmd::none()),
+        });
+        program.dispatch(Msg::Add(3));
+        program.dispatch(Msg::Double);
+        assert_eq!(program.model().sample(), 9);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn program_threaded() {
+        let program = Program::with_executor(0, update_threaded, Threaded);
+        let done = program.changes().filter(|n| *n >= 10).next();
+        program.dispatch(1);
+        assert_eq!(block_on(done), 10);
+    }
+
+    #[cfg(feature = "std")]
+    fn update_threaded(n: i32, msg: i32) -> (i32, Cmd<i32>) {
+        let n = n + msg;
+        if n < 10 {
+            (n, Cmd::msg(1))
+        } else {
+            (n, Cmd::none())
+        }
+    }
+}
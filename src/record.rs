@@ -0,0 +1,438 @@
+//! Recording and replaying of events.
+//!
+//! A `Recorder` taps into a `Sink` and writes every value sent to it along with a timestamp. The
+//! recorded events can be later loaded with a `Replayer` and sent again into a `Sink`, either as
+//! fast as possible or with the original timing. This allows reproducing a whole session
+//! deterministically, for example to debug an issue on a test.
+//!
+//! The events can be stored as JSON Lines (one JSON object per line) or in a compact binary
+//! format. The timestamps are measured with a `Clock`, so a `ManualClock` can be used to get
+//! reproducible times.
+//!
+//! # Example
+//! ```
+//! use frappe::record::{Format, Recorder, Replayer};
+//! use frappe::Sink;
+//!
+//! let sink = Sink::new();
+//! let recorder = Recorder::new(&sink, Vec::new(), Format::JsonLines);
+//! sink.send(1);
+//! sink.send(2);
+//! let data = recorder.finish().unwrap();
+//!
+//! let replayer = Replayer::read(&data[..], Format::JsonLines).unwrap();
+//! let sink2 = Sink::<i32>::new();
+//! let sum = sink2.stream().fold(0, |a, n| a + *n);
+//! replayer.replay(&sink2);
+//! assert_eq!(sum.sample(), 3);
+//! ```
+
+use crate::clock::{Clock, SystemClock};
+use crate::helpers::arc_and_weak;
+use crate::sync::Mutex;
+use crate::Sink;
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The storage format of the recorded events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// One JSON object per line.
+    JsonLines,
+    /// A sequence of binary encoded events.
+    Binary,
+}
+
+/// A recorded event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event<T> {
+    /// Time since the recording started.
+    #[serde(with = "nanos")]
+    pub at: Duration,
+    /// The value sent.
+    pub value: T,
+}
+
+/// Same layout as `Event`, used to serialize borrowed values.
+#[derive(Serialize)]
+struct EventRef<'a, T> {
+    #[serde(with = "nanos")]
+    at: Duration,
+    value: &'a T,
+}
+
+/// Stores durations as an amount of nanoseconds.
+mod nanos {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(d: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_u64(d.as_nanos() as u64)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        u64::deserialize(d).map(Duration::from_nanos)
+    }
+}
+
+/// The options used for the binary format.
+fn binary_options() -> impl Options {
+    bincode::DefaultOptions::new()
+}
+
+/// An error produced while recording or loading events.
+#[derive(Debug)]
+pub enum Error {
+    /// Error reading or writing the data.
+    Io(io::Error),
+    /// Error encoding or decoding JSON.
+    Json(serde_json::Error),
+    /// Error encoding or decoding the binary format.
+    Binary(bincode::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Json(e) => write!(f, "JSON error: {}", e),
+            Error::Binary(e) => write!(f, "binary format error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Binary(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    #[inline]
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    #[inline]
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+impl From<bincode::Error> for Error {
+    #[inline]
+    fn from(e: bincode::Error) -> Self {
+        Error::Binary(e)
+    }
+}
+
+/// The shared state of a `Recorder`.
+#[derive(Debug)]
+struct RecorderState<W> {
+    writer: Option<W>,
+    format: Format,
+    count: usize,
+    error: Option<Error>,
+}
+
+impl<W: Write> RecorderState<W> {
+    /// Writes an event, stopping at the first error.
+    fn write<T: Serialize>(&mut self, event: &EventRef<'_, T>) -> bool {
+        if self.error.is_some() {
+            return false;
+        }
+        let writer = match &mut self.writer {
+            Some(w) => w,
+            None => return false,
+        };
+        let result = match self.format {
+            Format::JsonLines => serde_json::to_writer(&mut *writer, event)
+                .map_err(Error::from)
+                .and_then(|_| writer.write_all(b"\n").map_err(Error::from)),
+            Format::Binary => binary_options()
+                .serialize_into(writer, event)
+                .map_err(Error::from),
+        };
+        match result {
+            Ok(()) => {
+                self.count += 1;
+                true
+            }
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+}
+
+/// Writes the values sent into a `Sink`.
+///
+/// The recording stops when the recorder is dropped, or after the first write error.
+#[derive(Debug)]
+pub struct Recorder<W> {
+    state: Arc<Mutex<RecorderState<W>>>,
+}
+
+impl<W: Write + Send + 'static> Recorder<W> {
+    /// Starts recording the values sent into `sink`, using the system time.
+    #[inline]
+    pub fn new<T>(sink: &Sink<T>, writer: W, format: Format) -> Self
+    where
+        T: Serialize + 'static,
+    {
+        Self::with_clock(sink, writer, format, SystemClock::new())
+    }
+
+    /// Starts recording the values sent into `sink`, using the specified clock.
+    ///
+    /// The timestamps are relative to the time when the recording started.
+    pub fn with_clock<T, C>(sink: &Sink<T>, writer: W, format: Format, clock: C) -> Self
+    where
+        T: Serialize + 'static,
+        C: Clock + 'static,
+    {
+        let (state, weak) = arc_and_weak(Mutex::new(RecorderState {
+            writer: Some(writer),
+            format,
+            count: 0,
+            error: None,
+        }));
+        let start = clock.now();
+        sink.stream().observe(move |val| {
+            // stop when the recorder is dropped or fails to write
+            let st = weak.upgrade()?;
+            let event = EventRef {
+                at: clock.now().saturating_sub(start),
+                value: &*val,
+            };
+            let ok = st.lock().write(&event);
+            ok.then_some(())
+        });
+        Recorder { state }
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    /// Gets the amount of events recorded.
+    pub fn count(&self) -> usize {
+        self.state.lock().count
+    }
+
+    /// Flushes the writer.
+    ///
+    /// Returns the write error that stopped the recording, if any.
+    pub fn flush(&self) -> Result<(), Error> {
+        let mut st = self.state.lock();
+        if let Some(e) = st.error.take() {
+            return Err(e);
+        }
+        match &mut st.writer {
+            Some(w) => w.flush().map_err(Error::from),
+            None => Ok(()),
+        }
+    }
+
+    /// Stops the recording and returns the writer.
+    pub fn finish(self) -> Result<W, Error> {
+        self.flush()?;
+        let writer = self.state.lock().writer.take();
+        Ok(writer.expect("writer taken"))
+    }
+}
+
+/// Sends recorded events into a `Sink`.
+#[derive(Debug, Clone)]
+pub struct Replayer<T> {
+    events: Vec<Event<T>>,
+}
+
+impl<T> Replayer<T> {
+    /// Creates a replayer from a list of events.
+    #[inline]
+    pub fn new(events: Vec<Event<T>>) -> Self {
+        Replayer { events }
+    }
+
+    /// Loads the events written by a `Recorder`.
+    pub fn read<R: Read>(reader: R, format: Format) -> Result<Self, Error>
+    where
+        T: DeserializeOwned,
+    {
+        let mut reader = BufReader::new(reader);
+        let mut events = Vec::new();
+        match format {
+            Format::JsonLines => {
+                for line in reader.lines() {
+                    let line = line?;
+                    if !line.trim().is_empty() {
+                        events.push(serde_json::from_str(&line)?);
+                    }
+                }
+            }
+            Format::Binary => {
+                while !reader.fill_buf()?.is_empty() {
+                    events.push(binary_options().deserialize_from(&mut reader)?);
+                }
+            }
+        }
+        Ok(Replayer { events })
+    }
+
+    /// Gets the loaded events.
+    #[inline]
+    pub fn events(&self) -> &[Event<T>] {
+        &self.events
+    }
+
+    /// Returns the loaded events.
+    #[inline]
+    pub fn into_events(self) -> Vec<Event<T>> {
+        self.events
+    }
+
+    /// Sends all the events into `sink` as fast as possible.
+    ///
+    /// The values are sent by reference, so the same events can be replayed many times.
+    pub fn replay(&self, sink: &Sink<T>) {
+        sink.feed(self.events.iter().map(|e| &e.value))
+    }
+
+    /// Sends all the events into `sink` with their original timing.
+    ///
+    /// The event times are relative to the clock time when this method is called. It blocks
+    /// until the last event is sent.
+    pub fn replay_timed<C: Clock>(&self, sink: &Sink<T>, clock: &C) {
+        let start = clock.now();
+        sink.feed(self.events.iter().map(|e| {
+            clock.sleep_until(start + e.at);
+            &e.value
+        }))
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::needless_borrows_for_generic_args)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum Input {
+        Click { x: i32, y: i32 },
+        Key(char),
+    }
+
+    fn session(clock: &Arc<ManualClock>, format: Format) -> Vec<u8> {
+        let sink = Sink::new();
+        let recorder = Recorder::with_clock(&sink, Vec::new(), format, clock.clone());
+        clock.advance(Duration::from_millis(10));
+        sink.send(Input::Click { x: 1, y: 2 });
+        clock.advance(Duration::from_millis(5));
+        sink.send(&Input::Key('a'));
+        clock.advance(Duration::from_millis(20));
+        sink.send(Input::Key('\n'));
+        assert_eq!(recorder.count(), 3);
+        recorder.finish().unwrap()
+    }
+
+    fn check_replay(data: &[u8], format: Format) {
+        let replayer = Replayer::<Input>::read(data, format).unwrap();
+        let times: Vec<_> = replayer.events().iter().map(|e| e.at).collect();
+        assert_eq!(
+            times,
+            [10, 15, 35]
+                .iter()
+                .map(|&ms| Duration::from_millis(ms))
+                .collect::<Vec<_>>()
+        );
+
+        let clock = Arc::new(ManualClock::new());
+        clock.set(Duration::from_secs(1));
+        let sink = Sink::new();
+        let clock_ = clock.clone();
+        let received = sink
+            .stream()
+            .map(move |e| (clock_.now(), Input::clone(&e)))
+            .collect::<Vec<_>>();
+
+        replayer.replay_timed(&sink, &clock);
+        replayer.replay(&sink);
+
+        let t = |ms| Duration::from_secs(1) + Duration::from_millis(ms);
+        let values: Vec<_> = replayer.events().iter().map(|e| e.value.clone()).collect();
+        assert_eq!(
+            received.sample(),
+            [
+                (t(10), values[0].clone()),
+                (t(15), values[1].clone()),
+                (t(35), values[2].clone()),
+                (t(35), values[0].clone()),
+                (t(35), values[1].clone()),
+                (t(35), values[2].clone()),
+            ]
+        );
+    }
+
+    #[test]
+    fn record_json_lines() {
+        let clock = Arc::new(ManualClock::new());
+        let data = session(&clock, Format::JsonLines);
+        let text = String::from_utf8(data.clone()).unwrap();
+        assert_eq!(
+            text,
+            "{\"at\":10000000,\"value\":{\"Click\":{\"x\":1,\"y\":2}}}\n\
+             {\"at\":15000000,\"value\":{\"Key\":\"a\"}}\n\
+             {\"at\":35000000,\"value\":{\"Key\":\"\\n\"}}\n"
+        );
+        check_replay(&data, Format::JsonLines);
+    }
+
+    #[test]
+    fn record_binary() {
+        let clock = Arc::new(ManualClock::new());
+        let data = session(&clock, Format::Binary);
+        assert!(data.len() < 40);
+        check_replay(&data, Format::Binary);
+    }
+
+    #[test]
+    fn record_errors() {
+        struct Failing;
+
+        impl Write for Failing {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk full"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let sink = Sink::new();
+        let recorder = Recorder::new(&sink, Failing, Format::JsonLines);
+        sink.send(1);
+        sink.send(2);
+        assert_eq!(recorder.count(), 0);
+        assert_eq!(sink.stream().stats().events_out, 1);
+        assert!(matches!(recorder.finish(), Err(Error::Json(_))));
+
+        let res =
+            Replayer::<i32>::read(&b"{\"at\":0,\"value\":1}\n{\"at\":"[..], Format::JsonLines);
+        assert!(matches!(res, Err(Error::Json(_))));
+        let res = Replayer::<i32>::read(&[200u8][..], Format::Binary);
+        assert!(matches!(res, Err(Error::Binary(_))));
+    }
+}
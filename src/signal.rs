@@ -8,6 +8,10 @@
 //! Signals are usually constructed by stream operations like `Stream::hold` and `Stream::fold`.
 //! They can also take values from a custom function by using `Signal::from_fn`.
 //!
+//! Sampling a signal can fail when it's value is missing, for example after a `Stream::fold`
+//! closure panicked. `Signal::sample` panics in that case, while `Signal::try_sample` returns a
+//! `SampleError` that can be handled (or replaced with a fallback using `Signal::or_else`).
+//!
 //! # Example
 //! ```
 //! use frappe::Sink;
@@ -26,16 +30,124 @@
 //! ```
 
 use crate::stream::Stream;
-use crate::sync::Mutex;
 use crate::types::{MaybeOwned, Storage};
-use std::fmt;
-use std::sync::{mpsc, Arc};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use core::fmt;
+use core::ops::Deref;
+
+#[cfg(feature = "std")]
+use crate::sync::Mutex;
+#[cfg(feature = "std")]
+use std::sync::mpsc;
 
 #[cfg(feature = "lazycell")]
 use lazycell::AtomicLazyCell;
 
+/// The reason a signal couldn't be sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleError {
+    /// The storage has no value.
+    Empty,
+    /// The forward declaration of a cyclic signal was sampled before it was defined.
+    Uninitialized,
+    /// A closure panicked while updating the storage, so it's value was lost.
+    ///
+    /// This is reported the same way with every lock backend.
+    Poisoned,
+}
+
+impl fmt::Display for SampleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SampleError::Empty => f.write_str("storage empty"),
+            SampleError::Uninitialized => f.write_str("sampled forward-declared Signal"),
+            SampleError::Poisoned => f.write_str("storage poisoned"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SampleError {}
+
+/// The source of the values of a signal.
+trait SignalFn<T>: Send + Sync {
+    /// Gets a copy of the value.
+    fn sample(&self) -> Result<T, SampleError>;
+
+    /// Gets a reference to the value, if it's stored somewhere. Otherwise it's computed.
+    fn sample_ref(&self) -> Result<SignalRef<'_, T>, SampleError> {
+        self.sample().map(|val| SignalRef(RefInner::Owned(val)))
+    }
+}
+
+/// A signal source that computes the value with a function.
+struct FnSignal<F>(F);
+
+impl<T, F> SignalFn<T> for FnSignal<F>
+where
+    F: Fn() -> Result<T, SampleError> + Send + Sync,
+{
+    #[inline]
+    fn sample(&self) -> Result<T, SampleError> {
+        (self.0)()
+    }
+}
+
+/// A signal source that reads the value from a storage.
+struct StorageSignal<T, S> {
+    storage: Arc<Storage<T>>,
+    _source: S,
+}
+
+impl<T, S> SignalFn<T> for StorageSignal<T, S>
+where
+    T: Clone + Send + Sync,
+    S: Send + Sync,
+{
+    #[inline]
+    fn sample(&self) -> Result<T, SampleError> {
+        self.storage.try_get()
+    }
+
+    fn sample_ref(&self) -> Result<SignalRef<'_, T>, SampleError> {
+        let val = self.storage.try_borrow()?;
+        Ok(SignalRef(RefInner::Borrowed(Box::new(val))))
+    }
+}
+
+/// A reference to the value of a signal.
+///
+/// If the signal is backed by a storage (like the ones created by `Stream::hold` or
+/// `Stream::fold`) the storage stays read-locked while this object is alive, so it should be
+/// dropped as soon as possible. Otherwise it contains a computed value.
+pub struct SignalRef<'a, T>(RefInner<'a, T>);
+
+enum RefInner<'a, T> {
+    Owned(T),
+    Borrowed(Box<dyn Deref<Target = T> + 'a>),
+}
+
+impl<T> Deref for SignalRef<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        match &self.0 {
+            RefInner::Owned(val) => val,
+            RefInner::Borrowed(val) => val,
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SignalRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SignalRef").field(&**self).finish()
+    }
+}
+
 /// Represents a value that changes over time.
-pub struct Signal<T>(Arc<dyn Fn() -> T + Send + Sync>);
+pub struct Signal<T>(Arc<dyn SignalFn<T>>);
 
 impl<T> Signal<T> {
     /// Creates a signal with constant value.
@@ -53,7 +165,18 @@ impl<T> Signal<T> {
     where
         F: Fn() -> T + Send + Sync + 'static,
     {
-        Signal(Arc::new(f))
+        Signal::from_try_fn(move || Ok(f()))
+    }
+
+    /// Creates a signal that samples it's values from a fallible source.
+    ///
+    /// The errors are returned by `Signal::try_sample`, and cause a panic on `Signal::sample`.
+    #[inline]
+    pub fn from_try_fn<F>(f: F) -> Self
+    where
+        F: Fn() -> Result<T, SampleError> + Send + Sync + 'static,
+    {
+        Signal(Arc::new(FnSignal(f)))
     }
 
     /// Creates a signal from shared storage.
@@ -63,19 +186,84 @@ impl<T> Signal<T> {
         T: Clone + Send + Sync + 'static,
         S: Send + Sync + 'static,
     {
-        Signal::from_fn(move || {
-            let _keepalive = &source;
-            storage.get()
-        })
+        Signal(Arc::new(StorageSignal {
+            storage,
+            _source: source,
+        }))
     }
 
     /// Samples the value of the signal.
     ///
     /// The action of sampling pulls the value through the signal chain until it finds it's source,
     /// clones it if necessary, and then transforms it into the result value.
+    ///
+    /// Panics if the value is missing. See `Signal::try_sample` for a version that doesn't panic.
     #[inline]
     pub fn sample(&self) -> T {
-        self.0()
+        match self.0.sample() {
+            Ok(val) => val,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Samples the value of the signal, or returns an error if it's missing.
+    ///
+    /// A signal created by `Stream::fold` (or a similar method) returns `SampleError::Poisoned`
+    /// after the fold closure panics.
+    #[inline]
+    pub fn try_sample(&self) -> Result<T, SampleError> {
+        self.0.sample()
+    }
+
+    /// Samples the value of the signal without cloning it.
+    ///
+    /// Signals backed by a storage return a reference to the stored value, and keep the storage
+    /// read-locked until the reference is dropped. Sending values to the stream that updates
+    /// the storage while holding the reference will deadlock. Other signals compute the value
+    /// as usual.
+    ///
+    /// Panics if the value is missing.
+    #[inline]
+    pub fn sample_ref(&self) -> SignalRef<'_, T> {
+        match self.0.sample_ref() {
+            Ok(val) => val,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Runs a closure with a reference to the value of the signal.
+    ///
+    /// This avoids cloning the value when the signal is backed by a storage. See
+    /// `Signal::sample_ref` for the locking caveats.
+    ///
+    /// Panics if the value is missing.
+    #[inline]
+    pub fn with<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        f(&self.sample_ref())
+    }
+
+    /// Runs a closure with a reference to the value of the signal, or returns an error if it's
+    /// missing.
+    #[inline]
+    pub fn try_with<F, R>(&self, f: F) -> Result<R, SampleError>
+    where
+        F: FnOnce(&T) -> R,
+    {
+        self.0.sample_ref().map(|val| f(&val))
+    }
+
+    /// Creates a signal that samples a fallback signal when this one can't be sampled.
+    ///
+    /// The fallback can be a signal or a constant value.
+    pub fn or_else(&self, fallback: impl Into<Signal<T>>) -> Signal<T>
+    where
+        T: 'static,
+    {
+        let (this, fallback) = (self.clone(), fallback.into());
+        Signal::from_try_fn(move || this.try_sample().or_else(|_| fallback.try_sample()))
     }
 
     /// Maps a signal using the provided function.
@@ -87,7 +275,21 @@ impl<T> Signal<T> {
         T: 'static,
     {
         let this = self.clone();
-        Signal::from_fn(move || f(this.sample()))
+        Signal::from_try_fn(move || this.try_sample().map(&f))
+    }
+
+    /// Maps a signal using a function that takes the value by reference.
+    ///
+    /// The function is applied with `Signal::with` every time the signal is sampled, so only the
+    /// result is cloned out of the source storage. This allows cheap projections of large values,
+    /// like getting the length of a vector.
+    pub fn map_ref<F, R>(&self, f: F) -> Signal<R>
+    where
+        F: Fn(&T) -> R + Send + Sync + 'static,
+        T: 'static,
+    {
+        let this = self.clone();
+        Signal::from_try_fn(move || this.try_with(&f))
     }
 
     /// Folds a signal using the provided function.
@@ -104,9 +306,9 @@ impl<T> Signal<T> {
     {
         let this = self.clone();
         let storage = Storage::new(initial);
-        Signal::from_fn(move || {
-            let val = this.sample();
-            storage.replace_fetch(|acc| f(acc, val))
+        Signal::from_try_fn(move || {
+            let val = this.try_sample()?;
+            storage.try_replace_fetch(|acc| f(acc, val))
         })
     }
 
@@ -126,6 +328,7 @@ impl<T> Signal<T> {
     ///
     /// When sampled, the resulting signal consumes all the current values on the channel
     /// (using `try_recv`) and returns the last value seen.
+    #[cfg(feature = "std")]
     #[inline]
     pub fn from_channel(initial: T, rx: mpsc::Receiver<T>) -> Self
     where
@@ -139,6 +342,7 @@ impl<T> Signal<T> {
     /// When sampled, the resulting signal consumes all the current values on the channel
     /// (using `try_recv`) and folds them using the current signal value as the
     /// initial accumulator state.
+    #[cfg(feature = "std")]
     pub fn fold_channel<V, F>(initial: T, rx: mpsc::Receiver<V>, f: F) -> Self
     where
         F: Fn(T, V) -> T + Send + Sync + 'static,
@@ -147,26 +351,66 @@ impl<T> Signal<T> {
     {
         let storage = Storage::new(initial);
         let rx = Mutex::new(rx);
-        Signal::from_fn(move || {
+        Signal::from_try_fn(move || {
             let source = rx.lock();
             if let Ok(first) = source.try_recv() {
-                storage.replace_fetch(|old| {
+                storage.try_replace_fetch(|old| {
                     let acc = f(old, first);
                     source.try_iter().fold(acc, &f)
                 })
             } else {
-                storage.get()
+                storage.try_get()
             }
         })
     }
 
+    /// Creates a tokio watch channel that follows the value of this signal.
+    ///
+    /// Signals are lazy, so they can't tell when their value changes. Instead the caller provides
+    /// a `changes` stream, and the signal is sampled every time it fires. It must fire after every
+    /// update of the values the signal depends on, so usually it's the stream the signal was
+    /// created from (like the one passed to `Stream::hold` or `Stream::fold`). Changes that aren't
+    /// followed by an event on `changes` are never seen by the receivers.
+    ///
+    /// The receivers are only notified when the sampled value is different, and the sampling
+    /// errors are skipped. The stream stops updating the channel when all the receivers are
+    /// dropped.
+    ///
+    /// Returns an error if the signal can't be sampled for the initial value of the channel.
+    #[cfg(feature = "tokio")]
+    pub fn to_watch<S>(
+        &self,
+        changes: &Stream<S>,
+    ) -> Result<tokio::sync::watch::Receiver<T>, SampleError>
+    where
+        T: PartialEq + Send + Sync + 'static,
+        S: 'static,
+    {
+        let (tx, rx) = tokio::sync::watch::channel(self.try_sample()?);
+        let this = self.clone();
+        changes.observe(move |_| {
+            if let Ok(val) = this.try_sample() {
+                tx.send_if_modified(|current| {
+                    let modified = *current != val;
+                    if modified {
+                        *current = val;
+                    }
+                    modified
+                });
+            }
+            !tx.is_closed()
+        });
+        Ok(rx)
+    }
+
     /// Creates a signal with a cyclic definition.
     ///
     /// This allows creating a self-referential Signal definition. The closure receives a forward
     /// declaration of a signal that must be used to construct the final Signal. Then this
     /// previous forward-declaration is replaced with the value returned by the closure.
     ///
-    /// Sampling the forward-declared signal will cause a panic.
+    /// Sampling the forward-declared signal will cause a panic (or a `SampleError::Uninitialized`
+    /// error on `Signal::try_sample`).
     #[cfg(feature = "lazycell")]
     pub fn cyclic<F>(definition: F) -> Self
     where
@@ -175,8 +419,10 @@ impl<T> Signal<T> {
     {
         let storage = Arc::new(AtomicLazyCell::new());
         let st = storage.clone();
-        let sig = Signal::from_fn(move || {
-            Signal::sample(st.borrow().expect("sampled forward-declared Signal"))
+        let sig = Signal::from_try_fn(move || {
+            st.borrow()
+                .ok_or(SampleError::Uninitialized)
+                .and_then(Signal::try_sample)
         });
         storage.fill(definition(&sig)).unwrap();
         sig
@@ -187,7 +433,7 @@ impl<T: 'static> Signal<Signal<T>> {
     /// Creates a new signal that samples the inner value of a nested signal.
     pub fn switch(&self) -> Signal<T> {
         let this = self.clone();
-        Signal::from_fn(move || this.sample().sample())
+        Signal::from_try_fn(move || this.try_sample()?.try_sample())
     }
 }
 
@@ -308,6 +554,88 @@ mod tests {
         assert_eq!(format!("{}", sig2), "13");
     }
 
+    #[test]
+    fn signal_try_sample() {
+        let sink = crate::Sink::new();
+        let sum = sink.stream().fold(0, |a, n| match *n {
+            n if n < 0 => panic!("negative value"),
+            n => a + n,
+        });
+        let doubled = sum.map(|x| x * 2);
+        let fallback = doubled.or_else(-1);
+        sink.send(2);
+        assert_eq!(doubled.try_sample(), Ok(4));
+        assert_eq!(fallback.sample(), 4);
+
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| sink.send(-1)));
+        assert!(res.is_err());
+        let err = SampleError::Poisoned;
+        assert_eq!(sum.try_sample(), Err(err));
+        // later sends are ignored instead of panicking again
+        sink.send(3);
+        assert_eq!(sum.try_sample(), Err(err));
+        assert_eq!(doubled.try_sample(), Err(err));
+        assert_eq!(fallback.sample(), -1);
+        assert_eq!(sum.or_else(Signal::from_fn(|| 7)).sample(), 7);
+        assert_eq!(
+            crate::signal_lift!(sum, fallback => |a, b| a + b).try_sample(),
+            Err(err)
+        );
+    }
+
+    #[test]
+    fn signal_with() {
+        /// Counts how many times it's cloned.
+        #[derive(Debug)]
+        struct Big(Vec<u32>, Arc<AtomicUsize>);
+
+        impl Clone for Big {
+            fn clone(&self) -> Self {
+                self.1.fetch_add(1, Ordering::Relaxed);
+                Big(self.0.clone(), self.1.clone())
+            }
+        }
+
+        let clones = Arc::new(AtomicUsize::new(0));
+        let sink = crate::Sink::new();
+        let sig = sink.stream().hold(Big(vec![], clones.clone()));
+        let len = sig.map_ref(|b| b.0.len());
+        sink.send(Big(vec![1, 2, 3], clones.clone()));
+
+        assert_eq!(sig.with(|b| b.0.len()), 3);
+        assert_eq!((*sig.sample_ref()).0, [1, 2, 3]);
+        assert_eq!(len.sample(), 3);
+        assert_eq!(clones.load(Ordering::Relaxed), 0);
+        assert_eq!(sig.sample().0.len(), 3);
+        assert_eq!(clones.load(Ordering::Relaxed), 1);
+
+        // computed signals work the same way
+        assert_eq!(Signal::constant(5).with(|x| x + 1), 6);
+        assert_eq!(len.map_ref(|n| n * 2).sample_ref().clone(), 6);
+        let missing = Signal::<i32>::from_try_fn(|| Err(SampleError::Empty));
+        assert_eq!(missing.try_with(|x| *x), Err(SampleError::Empty));
+    }
+
+    #[test]
+    #[should_panic(expected = "storage empty")]
+    fn signal_sample_empty() {
+        let sig = Signal::from_try_fn(|| Err::<i32, _>(SampleError::Empty));
+        sig.sample();
+    }
+
+    #[cfg(feature = "lazycell")]
+    #[test]
+    fn signal_cyclic_uninit() {
+        let mut early = None;
+        let sig = Signal::cyclic(|s| {
+            early = Some(s.try_sample());
+            Signal::constant(1)
+        });
+        assert_eq!(early, Some(Err(SampleError::Uninitialized)));
+        assert_eq!(sig.try_sample(), Ok(1));
+    }
+
+    #[cfg(feature = "std")]
     #[test]
     fn signal_channel() {
         let (tx, rx) = mpsc::channel();
@@ -319,4 +647,24 @@ mod tests {
 
         assert_eq!(sig.sample(), 55);
     }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn signal_to_watch() {
+        let sink = crate::Sink::new();
+        let stream = sink.stream();
+        let parity = stream.hold(0).map(|n| n % 2);
+        let mut rx = parity.to_watch(&stream).unwrap();
+
+        sink.send(2);
+        assert!(!rx.has_changed().unwrap());
+        sink.send(3);
+        rx.changed().await.unwrap();
+        assert_eq!(*rx.borrow_and_update(), 1);
+        sink.send(5);
+        assert!(!rx.has_changed().unwrap());
+
+        let empty = Signal::<i32>::from_try_fn(|| Err(SampleError::Empty));
+        assert_eq!(empty.to_watch(&stream).unwrap_err(), SampleError::Empty);
+    }
 }
@@ -17,6 +17,10 @@
 //! last one will receive a`MaybeOwned::Owned`. This also allows sending values as a reference with
 //! an arbitrary lifetime, not just `&'static` refs.
 //!
+//! Stream callbacks are allowed to send values and to create new streams from the same stream
+//! they're observing. Streams created while an event is being delivered will start receiving
+//! events after the current one has been fully processed.
+//!
 //! # Example
 //! ```
 //! use frappe::Sink;
@@ -34,19 +38,38 @@
 //! assert_eq!(signal.sample(), 20);
 //! ```
 
-use crate::futures::StreamFuture;
+use crate::collection::SignalVec;
+use crate::futures::{AsyncMap, Concurrency, Spawn, StreamFuture, StreamReceiver};
 use crate::helpers::arc_and_weak;
+use crate::history::HistorySignal;
 use crate::signal::Signal;
 use crate::sync::Mutex;
-use crate::types::{Callbacks, MaybeOwned, ObserveResult, Storage, SumType2};
-use std::any::Any;
-use std::collections::VecDeque;
-use std::ops::{Bound, RangeBounds};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
-
+use crate::trace;
+use crate::types::{ArcStorage, AtomicStorage, AtomicValue};
+use crate::types::{
+    CallbackHandle, Callbacks, MaybeOwned, NodeStats, ObserveResult, Storage, SumType2, Watcher,
+};
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+use core::fmt;
+use core::future::Future;
+use core::ops::{Bound, RangeBounds};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+#[cfg(feature = "serde")]
+use crate::persist::StateStore;
+#[cfg(any(feature = "crossbeam-utils", feature = "rayon"))]
+use crate::types::Chunking;
 #[cfg(feature = "either")]
 use crate::types::Either;
+#[cfg(feature = "std")]
+use crate::types::{BoundedQueue, OverflowPolicy, QueueWriter};
+#[cfg(feature = "serde")]
+use serde::{de::DeserializeOwned, Serialize};
+#[cfg(feature = "std")]
+use std::sync::mpsc;
+#[cfg(feature = "std")]
+use std::thread::{self, JoinHandle};
 
 /// A source of events that feeds the streams connected to it.
 #[derive(Debug)]
@@ -61,6 +84,13 @@ impl<T> Sink<T> {
         Default::default()
     }
 
+    /// Creates a sink that sends into a callback list.
+    #[inline]
+    fn with_callbacks(cbs: Arc<Callbacks<T>>) -> Self {
+        cbs.add_sender();
+        Sink { cbs }
+    }
+
     /// Creates a stream that receives the events sent to this sink.
     #[inline]
     pub fn stream(&self) -> Stream<T> {
@@ -76,7 +106,8 @@ impl<T> Sink<T> {
     where
         T: 'a,
     {
-        self.cbs.call(val)
+        let val = val.into();
+        trace::send(self.cbs.node(), val.is_owned()).in_scope(|| self.cbs.call(val))
     }
 
     /// Sends multiple values into the sink.
@@ -94,16 +125,92 @@ impl<T> Sink<T> {
 
     /// Sends a value using multiple threads.
     ///
-    /// This method sends a value to each of the Sink's connected streams simultaneously by spawning
-    /// a thread for each one, then it waits for all threads to finish. The value is sent by
-    /// reference, so no cloning is done.
-    #[cfg(feature = "crossbeam-utils")]
+    /// This method sends a value to each of the Sink's connected streams simultaneously, then it
+    /// waits for all of them to finish. The value is sent by reference, so no cloning is done.
+    ///
+    /// With the `rayon` feature enabled the work runs on the current rayon thread pool (use
+    /// `ThreadPool::install` to select a custom one). Otherwise a scoped thread is spawned for each
+    /// connected stream on every call, because the value is borrowed and can't be handed to
+    /// long-lived threads, so enable `rayon` if this is called often. This is the same as
+    /// `send_parallel_with(val, Chunking::PerCallback)`.
+    #[cfg(any(feature = "crossbeam-utils", feature = "rayon"))]
     #[inline]
     pub fn send_parallel(&self, val: &T)
     where
         T: Sync,
     {
-        self.cbs.call_parallel(val)
+        self.send_parallel_with(val, Chunking::default())
+    }
+
+    /// Sends a value using multiple threads, grouping the connected streams in jobs.
+    ///
+    /// This works like `Sink::send_parallel`, but the `chunking` strategy determines how many
+    /// streams are handled by each parallel job. Grouping reduces the overhead when the stream
+    /// callbacks are cheap to run.
+    #[cfg(any(feature = "crossbeam-utils", feature = "rayon"))]
+    #[inline]
+    pub fn send_parallel_with(&self, val: &T, chunking: Chunking)
+    where
+        T: Sync,
+    {
+        trace::send(self.cbs.node(), false).in_scope(|| self.cbs.call_parallel(val, chunking))
+    }
+
+    /// Creates a sink that receives the values from a tokio channel.
+    ///
+    /// The values are forwarded by a task spawned on the current runtime, that ends when all the
+    /// channel senders are dropped. Panics if it's not called from inside a tokio runtime.
+    #[cfg(feature = "tokio")]
+    pub fn from_mpsc(mut rx: tokio::sync::mpsc::Receiver<T>) -> Self
+    where
+        T: Send + 'static,
+    {
+        let sink = Sink::new();
+        let sink_ = sink.clone();
+        tokio::spawn(async move {
+            while let Some(val) = rx.recv().await {
+                sink_.send(val);
+            }
+        });
+        sink
+    }
+
+    /// Sends the values received from a channel into this sink, using a new thread.
+    ///
+    /// It accepts anything that can be iterated by blocking, like a `std::sync::mpsc::Receiver`
+    /// or a `crossbeam_channel::Receiver`. The values are read from the channel by a separate
+    /// thread and wait on a queue of size `capacity` until the observers can take them. When the
+    /// queue is full the value is handled according to `policy`, so with `OverflowPolicy::Block`
+    /// the channel's senders end up waiting for the observers.
+    ///
+    /// The returned handle is of the thread that sends into the sink. It ends after the channel is
+    /// disconnected and the queued values are sent.
+    #[cfg(feature = "std")]
+    pub fn pump_from<I>(
+        &self,
+        receiver: I,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> JoinHandle<()>
+    where
+        I: IntoIterator<Item = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let queue = Arc::new(BoundedQueue::with_consumer(capacity, policy));
+        let writer = QueueWriter::new(queue.clone());
+        thread::spawn(move || {
+            for val in receiver {
+                if !writer.push(val) {
+                    break;
+                }
+            }
+        });
+        let sink = self.clone();
+        thread::spawn(move || {
+            while let Some(val) = queue.pop() {
+                sink.send(val);
+            }
+        })
     }
 }
 
@@ -111,9 +218,7 @@ impl<T> Default for Sink<T> {
     /// Creates a new sink.
     #[inline]
     fn default() -> Self {
-        Self {
-            cbs: Default::default(),
-        }
+        Self::with_callbacks(Default::default())
     }
 }
 
@@ -121,21 +226,26 @@ impl<T> Clone for Sink<T> {
     /// Creates a copy of this sink that references the same event source.
     #[inline]
     fn clone(&self) -> Self {
-        Sink {
-            cbs: self.cbs.clone(),
-        }
+        Self::with_callbacks(self.cbs.clone())
+    }
+}
+
+impl<T> Drop for Sink<T> {
+    /// Closes the sink's streams if this was the last copy.
+    fn drop(&mut self) {
+        self.cbs.remove_sender();
     }
 }
 
 /// The source object of a Stream.
 ///
 /// This is used to create a strong reference to a parent stream.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 enum Source {
-    /// No source.
+    /// No source, the stream is fed by it's senders.
     None,
     /// The source is a type-erased object. Usually a stream of a different type.
-    Erased(Arc<dyn Any + Send + Sync>),
+    Erased(Arc<dyn Upstream>),
 }
 
 impl Source {
@@ -146,6 +256,67 @@ impl Source {
     fn stream2<A: 'static, B: 'static>(s1: &Stream<A>, s2: &Stream<B>) -> Self {
         Source::Erased(Arc::new((s1.clone(), s2.clone())))
     }
+
+    /// A source that can send values after it's closed, like the ones that run on another thread.
+    ///
+    /// The stream is considered open for as long as it lives.
+    fn deferred<T: 'static>(s: &Stream<T>) -> Self {
+        Source::Erased(Arc::new(Deferred(s.clone())))
+    }
+}
+
+impl fmt::Debug for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::None => f.write_str("None"),
+            Source::Erased(_) => f.write_str("Erased(..)"),
+        }
+    }
+}
+
+/// The parents of a stream.
+trait Upstream: Send + Sync {
+    /// Checks if the parents can still send values.
+    fn is_open(&self) -> bool;
+
+    /// Registers a watcher on the senders of the parents.
+    fn watch_senders(&self, watcher: &Weak<dyn Watcher>);
+}
+
+impl<T> Upstream for Stream<T> {
+    #[inline]
+    fn is_open(&self) -> bool {
+        Stream::is_open(self)
+    }
+
+    #[inline]
+    fn watch_senders(&self, watcher: &Weak<dyn Watcher>) {
+        Stream::watch_senders(self, watcher)
+    }
+}
+
+impl<A, B> Upstream for (Stream<A>, Stream<B>) {
+    fn is_open(&self) -> bool {
+        self.0.is_open() || self.1.is_open()
+    }
+
+    fn watch_senders(&self, watcher: &Weak<dyn Watcher>) {
+        self.0.watch_senders(watcher);
+        self.1.watch_senders(watcher);
+    }
+}
+
+/// A parent stream that is always considered open.
+struct Deferred<T>(#[allow(dead_code)] Stream<T>);
+
+impl<T> Upstream for Deferred<T> {
+    #[inline]
+    fn is_open(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn watch_senders(&self, _: &Weak<dyn Watcher>) {}
 }
 
 /// A stream of discrete events sent over time.
@@ -165,7 +336,62 @@ impl<T> Stream<T> {
     /// Creates a stream that never fires.
     #[inline]
     pub fn never() -> Self {
-        Stream::new(Default::default(), Source::None)
+        Stream::new(Arc::new(Callbacks::new("never", &[])), Source::None)
+    }
+
+    /// Gives a name to this stream.
+    ///
+    /// The name is shared by all the copies of this stream, and it's used to identify it on the
+    /// event graph (see the `debug` module).
+    pub fn named(self, name: &str) -> Self {
+        self.cbs.node().set_name(name);
+        self
+    }
+
+    /// Measures the time spent on the callbacks of this stream.
+    ///
+    /// This works like `debug::set_latency_tracking`, but only for this stream and it's copies.
+    #[cfg(feature = "std")]
+    pub fn track_latency(self, enabled: bool) -> Self {
+        self.cbs.node().set_latency_tracking(enabled);
+        self
+    }
+
+    /// Gets the event counters of this stream.
+    ///
+    /// The counters are shared by all the copies of this stream.
+    #[inline]
+    pub fn stats(&self) -> NodeStats {
+        self.cbs.node().stats()
+    }
+
+    /// Checks if this stream can still receive values.
+    ///
+    /// A stream is closed when all the sinks that feed it are dropped, including the `Sender`s
+    /// kept by `Stream::map_n` and `Stream::scan_n`. The streams that send values from other
+    /// threads or futures (like `Stream::buffered` and `Stream::map_async`) and `Stream::switch`
+    /// are always open while they live.
+    pub(crate) fn is_open(&self) -> bool {
+        match &self.source {
+            Source::None => self.cbs.has_senders(),
+            Source::Erased(parents) => self.cbs.has_senders() || parents.is_open(),
+        }
+    }
+
+    /// Registers a watcher that is notified when the sinks that feed this stream are dropped.
+    ///
+    /// It can be notified before the stream is closed, so `is_open` must be checked again.
+    pub(crate) fn watch_senders(&self, watcher: &Weak<dyn Watcher>) {
+        self.cbs.watch_senders(watcher.clone());
+        if let Source::Erased(parents) = &self.source {
+            parents.watch_senders(watcher);
+        }
+    }
+
+    /// Gets the id of this stream on the event graph.
+    #[inline]
+    pub(crate) fn node_id(&self) -> usize {
+        self.cbs.node().id()
     }
 
     /// Reads the values from the stream.
@@ -206,6 +432,20 @@ impl<T> Stream<T> {
         });
     }
 
+    /// Observes the stream with a closure that can be removed with `Stream::unobserve`.
+    pub(crate) fn observe_removable<F>(&self, f: F) -> CallbackHandle<T>
+    where
+        F: Fn(MaybeOwned<'_, T>) -> bool + Send + Sync + 'static,
+    {
+        self.cbs.push_removable(f)
+    }
+
+    /// Removes an observer added by `Stream::observe_removable`.
+    #[inline]
+    pub(crate) fn unobserve(&self, handle: CallbackHandle<T>) {
+        self.cbs.remove(handle)
+    }
+
     /// Chainable version of `Stream::observe`.
     #[inline]
     pub fn inspect<F, R>(self, f: F) -> Self
@@ -228,7 +468,9 @@ impl<T: 'static> Stream<T> {
         F: Fn(MaybeOwned<'_, T>) -> R + Send + Sync + 'static,
         R: 'static,
     {
-        self.filter_map(move |arg| Some(f(arg)))
+        let stream = self.filter_map(move |arg| Some(f(arg)));
+        stream.cbs.node().set_operator("map");
+        stream
     }
 
     /// Creates a new stream that only contains the values where the predicate is `true`.
@@ -236,7 +478,7 @@ impl<T: 'static> Stream<T> {
     where
         F: Fn(&T) -> bool + Send + Sync + 'static,
     {
-        let (new_cbs, weak) = arc_and_weak(Callbacks::new());
+        let (new_cbs, weak) = arc_and_weak(Callbacks::new("filter", &[self.node_id()]));
         self.cbs.push(move |arg| {
             with_weak!(weak, |cb| if pred(&arg) {
                 cb.call(arg)
@@ -253,7 +495,7 @@ impl<T: 'static> Stream<T> {
         F: Fn(MaybeOwned<'_, T>) -> Option<R> + Send + Sync + 'static,
         R: 'static,
     {
-        let (new_cbs, weak) = arc_and_weak(Callbacks::new());
+        let (new_cbs, weak) = arc_and_weak(Callbacks::new("filter_map", &[self.node_id()]));
         self.cbs.push(move |arg| {
             with_weak!(weak, |cb| if let Some(val) = f(arg) {
                 cb.call(val)
@@ -264,7 +506,8 @@ impl<T: 'static> Stream<T> {
 
     /// Creates a new stream that fires with the events from both streams.
     pub fn merge(&self, other: &Stream<T>) -> Self {
-        let (new_cbs, weak1) = arc_and_weak(Callbacks::new());
+        let (new_cbs, weak1) =
+            arc_and_weak(Callbacks::new("merge", &[self.node_id(), other.node_id()]));
         let weak2 = weak1.clone();
         self.cbs
             .push(move |arg| with_weak!(weak1, |cb| cb.call(arg)));
@@ -286,7 +529,10 @@ impl<T: 'static> Stream<T> {
         U: 'static,
         R: 'static,
     {
-        let (new_cbs, weak1) = arc_and_weak(Callbacks::new());
+        let (new_cbs, weak1) = arc_and_weak(Callbacks::new(
+            "merge_with",
+            &[self.node_id(), other.node_id()],
+        ));
         let weak2 = weak1.clone();
         self.cbs
             .push(move |arg| with_weak!(weak1, |cb| cb.call(f1(arg))));
@@ -321,14 +567,15 @@ impl<T: 'static> Stream<T> {
     /// The fold operation is done by taking the accumulator, consuming it's value, and then
     /// putting back the transformed value. This avoids cloning, but if the closure panics it will
     /// leave the storage empty, and then any sampling attempt on this object will panic until
-    /// someone puts back a value on it.
+    /// someone puts back a value on it (`Signal::try_sample` returns an error instead). The values
+    /// sent while the storage is empty are ignored.
     /// If this is undesirable, use `Stream::fold_clone` instead.
     pub fn fold<A, F>(&self, initial: A, f: F) -> Signal<A>
     where
         F: Fn(A, MaybeOwned<'_, T>) -> A + Send + Sync + 'static,
         A: Clone + Send + Sync + 'static,
     {
-        let (storage, weak) = arc_and_weak(Storage::new(initial));
+        let (storage, weak) = arc_and_weak(Storage::with_node(initial, "fold", self.node_id()));
         self.cbs.push(move |arg| {
             with_weak!(weak, |st| {
                 st.replace(|old| f(old, arg));
@@ -337,6 +584,30 @@ impl<T: 'static> Stream<T> {
         Signal::from_storage(storage, self.clone())
     }
 
+    /// Accumulates the values sent over this stream into an atomic.
+    ///
+    /// This works like `Stream::fold`, but the accumulator is stored in an atomic so the signal
+    /// can be sampled without locking. If multiple threads send values at the same time, the
+    /// closure can be called more than once for the same value, so it should have no side effects.
+    pub fn fold_atomic<A, F>(&self, initial: A, f: F) -> Signal<A>
+    where
+        F: Fn(A, &T) -> A + Send + Sync + 'static,
+        A: AtomicValue,
+    {
+        let (storage, weak) = arc_and_weak(AtomicStorage::with_node(
+            initial,
+            "fold_atomic",
+            self.node_id(),
+        ));
+        self.cbs
+            .push(move |arg| with_weak!(weak, |st| st.update(|acc| f(acc, &arg))));
+        let source = self.clone();
+        Signal::from_fn(move || {
+            let _keepalive = &source;
+            storage.get()
+        })
+    }
+
     /// Folds the stream by cloning the accumulator.
     ///
     /// This does the same as `Stream::fold` but it will clone the accumulator on every value
@@ -347,7 +618,8 @@ impl<T: 'static> Stream<T> {
         F: Fn(A, MaybeOwned<'_, T>) -> A + Send + Sync + 'static,
         A: Clone + Send + Sync + 'static,
     {
-        let (storage, weak) = arc_and_weak(Storage::new(initial));
+        let (storage, weak) =
+            arc_and_weak(Storage::with_node(initial, "fold_clone", self.node_id()));
         self.cbs.push(move |arg| {
             with_weak!(weak, |st| {
                 st.replace_clone(|old| f(old, arg));
@@ -356,6 +628,90 @@ impl<T: 'static> Stream<T> {
         Signal::from_storage(storage, self.clone())
     }
 
+    /// Accumulates the values sent over this stream, saving the accumulator on a `StateStore`.
+    ///
+    /// This works like `Stream::fold`, but the accumulator is registered on `store` under `key`,
+    /// so it's included on the store snapshots. If the store has a restored value for `key`, it's
+    /// used instead of `initial`.
+    #[cfg(feature = "serde")]
+    pub fn fold_persistent<A, F>(
+        &self,
+        store: &StateStore,
+        key: &str,
+        initial: A,
+        f: F,
+    ) -> Signal<A>
+    where
+        F: Fn(A, MaybeOwned<'_, T>) -> A + Send + Sync + 'static,
+        A: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    {
+        let (storage, weak) = arc_and_weak(Storage::with_node(
+            initial,
+            "fold_persistent",
+            self.node_id(),
+        ));
+        store.register(key, &storage);
+        self.cbs.push(move |arg| {
+            with_weak!(weak, |st| {
+                st.replace(|old| f(old, arg));
+            })
+        });
+        Signal::from_storage(storage, self.clone())
+    }
+
+    /// Accumulates the values sent over this stream, keeping a history of the changes.
+    ///
+    /// This works like `Stream::fold_clone`, but a copy of the accumulator is saved before every
+    /// change so it can be restored with `HistorySignal::undo`. Up to `capacity` changes are
+    /// remembered.
+    pub fn fold_with_history<A, F>(&self, initial: A, f: F, capacity: usize) -> HistorySignal<A>
+    where
+        F: Fn(A, MaybeOwned<'_, T>) -> A + Send + Sync + 'static,
+        A: Clone + Send + Sync + 'static,
+    {
+        let swap = |state, entry| (entry, state);
+        HistorySignal::new(
+            self,
+            "fold_with_history",
+            initial,
+            capacity,
+            move |state: A, arg| (f(state.clone(), arg), state),
+            swap,
+            swap,
+        )
+    }
+
+    /// Accumulates the values sent over this stream, keeping a history of the events.
+    ///
+    /// The events are applied with `apply`, and they're saved so they can be reverted with
+    /// `revert` when `HistorySignal::undo` is called. Up to `capacity` events are remembered.
+    /// This avoids copying the accumulator, so it's better for large states.
+    pub fn fold_with_commands<A, F, R>(
+        &self,
+        initial: A,
+        apply: F,
+        revert: R,
+        capacity: usize,
+    ) -> HistorySignal<A>
+    where
+        F: Fn(A, &T) -> A + Send + Sync + 'static,
+        R: Fn(A, &T) -> A + Send + Sync + 'static,
+        A: Clone + Send + Sync + 'static,
+        T: Clone + Send,
+    {
+        let apply = Arc::new(apply);
+        let apply2 = apply.clone();
+        HistorySignal::new(
+            self,
+            "fold_with_commands",
+            initial,
+            capacity,
+            move |state, arg: MaybeOwned<'_, T>| (apply(state, &arg), arg.into_owned()),
+            move |state, cmd| (revert(state, &cmd), cmd),
+            move |state, cmd| (apply2(state, &cmd), cmd),
+        )
+    }
+
     /// Maps each stream event to `0..N` output values.
     ///
     /// On every stream event received the closure must return its value by sending it through the
@@ -368,7 +724,7 @@ impl<T: 'static> Stream<T> {
         F: Fn(MaybeOwned<'_, T>, Sender<R>) + Send + Sync + 'static,
         R: 'static,
     {
-        let (new_cbs, weak) = arc_and_weak(Callbacks::new());
+        let (new_cbs, weak) = arc_and_weak(Callbacks::new("map_n", &[self.node_id()]));
         self.cbs
             .push(move |arg| with_weak!(weak, |cb| f(arg, Sender::new(cb))));
         Stream::new(new_cbs, Source::stream(self))
@@ -383,12 +739,14 @@ impl<T: 'static> Stream<T> {
         F: Fn(A, MaybeOwned<'_, T>) -> A + Send + Sync + 'static,
         A: Clone + Send + Sync + 'static,
     {
-        let (new_cbs, weak) = arc_and_weak(Callbacks::new());
+        let (new_cbs, weak) = arc_and_weak(Callbacks::new("scan", &[self.node_id()]));
         let storage = Storage::new(initial);
         self.cbs.push(move |arg| {
             with_weak!(weak, |cb| {
-                let new = storage.replace_fetch(|old| f(old, arg));
-                cb.call(new)
+                // a previous panic in the closure stops the output
+                if let Ok(new) = storage.try_replace_fetch(|old| f(old, arg)) {
+                    cb.call(new)
+                }
             })
         });
         Stream::new(new_cbs, Source::stream(self))
@@ -405,7 +763,7 @@ impl<T: 'static> Stream<T> {
         A: Send + Sync + 'static,
         R: 'static,
     {
-        let (new_cbs, weak) = arc_and_weak(Callbacks::new());
+        let (new_cbs, weak) = arc_and_weak(Callbacks::new("scan_n", &[self.node_id()]));
         let storage = Storage::new(initial);
         self.cbs.push(move |arg| {
             with_weak!(weak, |cb| storage.replace(|old| f(
@@ -424,18 +782,37 @@ impl<T: 'static> Stream<T> {
         C: Default + Extend<T> + Clone + Send + Sync + 'static,
         T: Clone,
     {
-        self.fold(C::default(), |mut a, v| {
-            a.extend(Some(v.into_owned()));
-            a
-        })
+        let (storage, weak) =
+            arc_and_weak(Storage::with_node(C::default(), "collect", self.node_id()));
+        self.cbs.push(move |arg| {
+            with_weak!(weak, |st| {
+                st.replace(|mut a| {
+                    a.extend(Some(arg.into_owned()));
+                    a
+                });
+            })
+        });
+        Signal::from_storage(storage, self.clone())
+    }
+
+    /// Creates a vector signal from the values sent to this stream.
+    ///
+    /// Unlike `collect`, the resulting vector can be read without copying it, and it reports the
+    /// values added as `VecDiff` events.
+    #[inline]
+    pub fn collect_vec(&self) -> SignalVec<T>
+    where
+        T: Clone + Send + Sync,
+    {
+        SignalVec::collect(self)
     }
 
     /// Returns a stream that contains only the Nth value from the input stream.
     pub fn element_at(&self, index: usize) -> Self {
-        let (new_cbs, weak) = arc_and_weak(Callbacks::new());
+        let (new_cbs, weak) = arc_and_weak(Callbacks::new("element_at", &[self.node_id()]));
         let pos = AtomicUsize::new(0);
         self.cbs.push(move |arg| {
-            weak.upgrade().map_or(false, |cb| {
+            weak.upgrade().is_some_and(|cb| {
                 let cur_pos = pos.fetch_add(1, Ordering::Relaxed);
                 if cur_pos == index {
                     cb.call(arg);
@@ -451,10 +828,10 @@ impl<T: 'static> Stream<T> {
     where
         B: RangeBounds<usize> + Send + Sync + 'static,
     {
-        let (new_cbs, weak) = arc_and_weak(Callbacks::new());
+        let (new_cbs, weak) = arc_and_weak(Callbacks::new("elements_between", &[self.node_id()]));
         let pos = AtomicUsize::new(0);
         self.cbs.push(move |arg| {
-            weak.upgrade().map_or(false, |cb| {
+            weak.upgrade().is_some_and(|cb| {
                 let cur_pos = pos.fetch_add(1, Ordering::Relaxed);
                 let after_start = match range.start_bound() {
                     Bound::Included(s) => cur_pos >= *s,
@@ -477,6 +854,26 @@ impl<T: 'static> Stream<T> {
 }
 
 impl<T: Clone + Send + 'static> Stream<T> {
+    /// Creates a stream with a cyclic definition.
+    ///
+    /// The closure receives a forward declaration of the stream that it must use to build the
+    /// final stream. The events of the returned stream are then sent back into the forward
+    /// declaration. See `StreamLoop` for the details of how the values are fed back.
+    ///
+    /// The cycle is owned by the returned stream, so it's disconnected when the stream is dropped.
+    pub fn cyclic<F>(definition: F) -> Self
+    where
+        F: FnOnce(&Stream<T>) -> Stream<T>,
+    {
+        let cbs = Arc::new(Callbacks::new("loop", &[]));
+        let def = definition(&Stream::new(cbs.clone(), Source::None));
+        let (new_cbs, weak) = arc_and_weak(Callbacks::new("cyclic", &[def.node_id()]));
+        // registered before the feedback, so the observers see the values in order
+        def.cbs.push(move |arg| with_weak!(weak, |cb| cb.call(arg)));
+        feed_back(&def, &cbs);
+        Stream::new(new_cbs, Source::stream(&def))
+    }
+
     /// Creates a Signal that holds the last value sent to this stream.
     #[inline]
     pub fn hold(&self, initial: T) -> Signal<T>
@@ -486,13 +883,54 @@ impl<T: Clone + Send + 'static> Stream<T> {
         self.hold_if(initial, |_| true)
     }
 
+    /// Creates a Signal that holds the last value sent to this stream in an atomic.
+    ///
+    /// This works like `Stream::hold`, but the signal can be sampled without locking. It's
+    /// available for `bool`, integers and floats.
+    pub fn hold_atomic(&self, initial: T) -> Signal<T>
+    where
+        T: AtomicValue,
+    {
+        let (storage, weak) = arc_and_weak(AtomicStorage::with_node(
+            initial,
+            "hold_atomic",
+            self.node_id(),
+        ));
+        self.cbs
+            .push(move |arg| with_weak!(weak, |st| st.set(*arg)));
+        let source = self.clone();
+        Signal::from_fn(move || {
+            let _keepalive = &source;
+            storage.get()
+        })
+    }
+
+    /// Creates a Signal that holds a shared reference to the last value sent to this stream.
+    ///
+    /// Sampling the signal only clones the `Arc`, so this is efficient for large values that
+    /// are sampled often. With the `arc-swap` feature enabled, sampling is also lock-free.
+    pub fn hold_arc(&self, initial: T) -> Signal<Arc<T>>
+    where
+        T: Sync,
+    {
+        let (storage, weak) =
+            arc_and_weak(ArcStorage::with_node(initial, "hold_arc", self.node_id()));
+        self.cbs
+            .push(move |arg| with_weak!(weak, |st| st.set(arg.into_owned())));
+        let source = self.clone();
+        Signal::from_fn(move || {
+            let _keepalive = &source;
+            storage.get()
+        })
+    }
+
     /// Holds the last value in this stream where the predicate is `true`.
     pub fn hold_if<F>(&self, initial: T, pred: F) -> Signal<T>
     where
         F: Fn(&T) -> bool + Send + Sync + 'static,
         T: Sync,
     {
-        let (storage, weak) = arc_and_weak(Storage::new(initial));
+        let (storage, weak) = arc_and_weak(Storage::with_node(initial, "hold", self.node_id()));
         self.cbs.push(move |arg| {
             with_weak!(weak, |st| if pred(&arg) {
                 st.set(arg.into_owned());
@@ -521,7 +959,10 @@ impl<T: Clone + Send + 'static> Stream<T> {
         U: Clone + Send + 'static,
         R: 'static,
     {
-        let (new_cbs, weak1) = arc_and_weak(Callbacks::new());
+        let (new_cbs, weak1) = arc_and_weak(Callbacks::new(
+            "zip_with",
+            &[self.node_id(), other.node_id()],
+        ));
         let weak2 = weak1.clone();
 
         let left = Arc::new(Mutex::new(VecDeque::new()));
@@ -569,7 +1010,10 @@ impl<T: Clone + Send + 'static> Stream<T> {
         U: Clone + Send + 'static,
         R: 'static,
     {
-        let (new_cbs, weak1) = arc_and_weak(Callbacks::new());
+        let (new_cbs, weak1) = arc_and_weak(Callbacks::new(
+            "combine_with",
+            &[self.node_id(), other.node_id()],
+        ));
         let weak2 = weak1.clone();
 
         let left = Arc::new(Mutex::new(None));
@@ -606,6 +1050,159 @@ impl<T: Clone + Send + 'static> Stream<T> {
     pub fn next(&self) -> StreamFuture<T> {
         StreamFuture::new(self.clone())
     }
+
+    /// Creates a future that returns the next value sent to this stream that matches a predicate.
+    pub fn next_matching<F>(&self, pred: F) -> StreamFuture<T>
+    where
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        StreamFuture::with_predicate(self.clone(), Some(Arc::new(pred)))
+    }
+
+    /// Creates a receiver that queues the values sent to this stream.
+    #[inline]
+    pub fn receiver(&self) -> StreamReceiver<T> {
+        StreamReceiver::new(self.clone())
+    }
+
+    /// Maps the values of this stream with an async function.
+    ///
+    /// The futures are run by the spawner, and their results are sent into the new stream from
+    /// the thread that completes them. The `concurrency` mode limits how many futures run at the
+    /// same time, and determines the order of the results. Values that arrive while the limit is
+    /// reached wait for a running future to finish.
+    pub fn map_async<F, Fut, R, S>(&self, f: F, concurrency: Concurrency, spawner: S) -> Stream<R>
+    where
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: Send + 'static,
+        S: Spawn + 'static,
+    {
+        let (new_cbs, weak) = arc_and_weak(Callbacks::new("map_async", &[self.node_id()]));
+        let map = AsyncMap::new(f, concurrency, spawner, weak);
+        self.cbs.push(move |arg| map.push(arg.into_owned()));
+        Stream::new(new_cbs, Source::deferred(self))
+    }
+
+    /// Maps the values of this stream with an async function, one at a time.
+    ///
+    /// This is the same as `Stream::map_async` with `Concurrency::Ordered(1)`.
+    #[inline]
+    pub fn then<F, Fut, R, S>(&self, f: F, spawner: S) -> Stream<R>
+    where
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = R> + Send + 'static,
+        R: Send + 'static,
+        S: Spawn + 'static,
+    {
+        self.map_async(f, Concurrency::Ordered(1), spawner)
+    }
+
+    /// Sends the values of this stream into a tokio broadcast channel.
+    ///
+    /// The stream stops sending when all the receivers are dropped. Panics if `capacity` is zero.
+    #[cfg(feature = "tokio")]
+    pub fn to_broadcast(&self, capacity: usize) -> tokio::sync::broadcast::Receiver<T> {
+        let (tx, rx) = tokio::sync::broadcast::channel(capacity);
+        self.observe(move |val| tx.send(val.into_owned()).is_ok());
+        rx
+    }
+
+    /// Creates a stream that receives the values of this stream from another thread.
+    ///
+    /// The values wait on a queue of size `capacity`, and a dispatcher thread sends them into the
+    /// new stream. This way a slow observer doesn't block the sender. When the queue is full the
+    /// value is handled according to `policy`. The returned signal has the amount of values
+    /// waiting on the queue.
+    ///
+    /// With `OverflowPolicy::Block` the sender waits for the dispatcher, so the new stream's
+    /// observers must not send back into this stream.
+    #[cfg(feature = "std")]
+    pub fn buffered(&self, capacity: usize, policy: OverflowPolicy) -> (Self, Signal<usize>) {
+        let (new_cbs, weak) = arc_and_weak(Callbacks::new("buffered", &[self.node_id()]));
+        let queue = forward_through_queue(self, capacity, policy, move |val| {
+            with_weak!(weak, |cb| cb.call(val))
+        });
+        let depth = Signal::from_fn(move || queue.len());
+        (Stream::new(new_cbs, Source::deferred(self)), depth)
+    }
+
+    /// Sends the values of this stream into a bounded channel.
+    ///
+    /// When the channel is full the value is handled according to `policy`. The stream stops
+    /// sending when the receiver is dropped.
+    ///
+    /// With `OverflowPolicy::DropOldest` and `OverflowPolicy::CoalesceLatest` a new thread moves
+    /// the values into the channel. The thread holds the oldest value until it's received, and the
+    /// latest `bound - 1` values (at least one) wait on a queue behind it. The thread ends when the
+    /// stream is dropped, or at the first value sent after the receiver is dropped, because the
+    /// channel can't report a disconnection until then.
+    #[cfg(feature = "std")]
+    pub fn to_sync_channel(&self, bound: usize, policy: OverflowPolicy) -> mpsc::Receiver<T> {
+        match policy {
+            OverflowPolicy::Block => {
+                let (tx, rx) = mpsc::sync_channel(bound);
+                self.observe(move |val| tx.send(val.into_owned()));
+                rx
+            }
+            OverflowPolicy::DropNewest => {
+                let (tx, rx) = mpsc::sync_channel(bound);
+                self.observe(move |val| {
+                    !matches!(
+                        tx.try_send(val.into_owned()),
+                        Err(mpsc::TrySendError::Disconnected(_))
+                    )
+                });
+                rx
+            }
+            OverflowPolicy::DropOldest | OverflowPolicy::CoalesceLatest => {
+                // the value held by the thread counts towards the bound
+                let (tx, rx) = mpsc::sync_channel(0);
+                forward_through_queue(self, bound.saturating_sub(1), policy, move |val| {
+                    tx.send(val).is_ok()
+                });
+                rx
+            }
+        }
+    }
+
+    /// Sends the values of this stream into a bounded crossbeam channel.
+    ///
+    /// This works like `Stream::to_sync_channel`.
+    #[cfg(feature = "crossbeam-channel")]
+    pub fn to_crossbeam(
+        &self,
+        bound: usize,
+        policy: OverflowPolicy,
+    ) -> crossbeam_channel::Receiver<T> {
+        use crossbeam_channel::{bounded, TrySendError};
+
+        match policy {
+            OverflowPolicy::Block => {
+                let (tx, rx) = bounded(bound);
+                self.observe(move |val| tx.send(val.into_owned()));
+                rx
+            }
+            OverflowPolicy::DropNewest => {
+                let (tx, rx) = bounded(bound);
+                self.observe(move |val| {
+                    !matches!(
+                        tx.try_send(val.into_owned()),
+                        Err(TrySendError::Disconnected(_))
+                    )
+                });
+                rx
+            }
+            OverflowPolicy::DropOldest | OverflowPolicy::CoalesceLatest => {
+                // the value held by the thread counts towards the bound
+                let (tx, rx) = bounded(0);
+                forward_through_queue(self, bound.saturating_sub(1), policy, move |val| {
+                    tx.send(val).is_ok()
+                });
+                rx
+            }
+        }
+    }
 }
 
 impl<T: Clone + 'static> Stream<Option<T>> {
@@ -659,8 +1256,8 @@ where
 
     /// Splits a two element sum type stream into two streams with the unwrapped values.
     pub fn split(&self) -> (Stream<T::Type1>, Stream<T::Type2>) {
-        let (cbs_1, weak_1) = arc_and_weak(Callbacks::new());
-        let (cbs_2, weak_2) = arc_and_weak(Callbacks::new());
+        let (cbs_1, weak_1) = arc_and_weak(Callbacks::new("split", &[self.node_id()]));
+        let (cbs_2, weak_2) = arc_and_weak(Callbacks::new("split", &[self.node_id()]));
         self.cbs.push(move |result| {
             if result.is_type1() {
                 if let Some(cb) = weak_1.upgrade() {
@@ -690,7 +1287,7 @@ where
 impl<T: 'static> Stream<Stream<T>> {
     /// Listens to the events from the last stream sent to a nested stream.
     pub fn switch(&self) -> Stream<T> {
-        let (new_cbs, weak) = arc_and_weak(Callbacks::new());
+        let (new_cbs, weak) = arc_and_weak(Callbacks::new("switch", &[self.node_id()]));
         let id = Arc::new(AtomicUsize::new(0)); // id of each stream sent
         self.cbs.push(move |stream| {
             if weak.upgrade().is_none() {
@@ -709,7 +1306,7 @@ impl<T: 'static> Stream<Stream<T>> {
             });
             true
         });
-        Stream::new(new_cbs, Source::stream(self))
+        Stream::new(new_cbs, Source::deferred(self))
     }
 }
 
@@ -742,7 +1339,7 @@ impl<T> Sender<T> {
     /// Constructs a new Sender from a list of callbacks.
     #[inline]
     fn new(cbs: Arc<Callbacks<T>>) -> Self {
-        Sender(Sink { cbs })
+        Sender(Sink::with_callbacks(cbs))
     }
 
     /// Sends a value.
@@ -766,7 +1363,171 @@ impl<T> Clone for Sender<T> {
     }
 }
 
+/// Sends the values of a stream back into the callbacks of a forward declared stream.
+///
+/// Values that arrive while a fed back value is being processed are queued and sent after it, so
+/// a feedback loop doesn't grow the stack on every iteration.
+///
+/// Returns the handle of the observer registered on `from`, so the feedback can be removed.
+fn feed_back<T: Clone + Send + 'static>(
+    from: &Stream<T>,
+    to: &Arc<Callbacks<T>>,
+) -> CallbackHandle<T> {
+    let weak = Arc::downgrade(to);
+    let queue = Mutex::new(VecDeque::new());
+    let sending = AtomicBool::new(false);
+    from.cbs.push_removable(move |arg| {
+        with_weak!(weak, |cb| {
+            queue.lock().push_back(arg.into_owned());
+            while !sending.swap(true, Ordering::SeqCst) {
+                loop {
+                    let next = queue.lock().pop_front();
+                    match next {
+                        Some(val) => cb.call(val),
+                        None => break,
+                    }
+                }
+                sending.store(false, Ordering::SeqCst);
+                if queue.lock().is_empty() {
+                    break;
+                }
+            }
+        })
+    })
+}
+
+/// Sends the values of a stream to `send` from a new thread, through a bounded queue.
+///
+/// The thread ends when `send` returns `false` or when the stream is dropped. The stream stops
+/// sending into the queue after that.
+#[cfg(feature = "std")]
+fn forward_through_queue<T, F>(
+    stream: &Stream<T>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    send: F,
+) -> Arc<BoundedQueue<T>>
+where
+    T: Clone + Send + 'static,
+    F: Fn(T) -> bool + Send + 'static,
+{
+    let queue = Arc::new(BoundedQueue::with_consumer(capacity, policy));
+    let writer = QueueWriter::new(queue.clone());
+    stream.observe(move |val| writer.push(val.into_owned()));
+    let q = queue.clone();
+    thread::spawn(move || {
+        while let Some(val) = q.pop() {
+            if !send(val) {
+                q.close();
+                break;
+            }
+        }
+    });
+    queue
+}
+
+/// A forward declaration of a stream.
+///
+/// This allows creating feedback loops, where the events of a stream are sent back into one of
+/// it's sources. The stream returned by `StreamLoop::stream` can be used before it's defined, and
+/// once the defining stream is built it's connected with `StreamLoop::define`.
+///
+/// The loop keeps the defining stream alive, but the streams created from the forward declaration
+/// don't keep the loop alive. Dropping the `StreamLoop` removes the feedback from the defining
+/// stream, even if other copies of it are still alive, so the cycle doesn't leak. See also `Stream::cyclic` for loops that can be built in a single expression.
+///
+/// The feedback is synchronous, so the loop must eventually stop sending values back (for
+/// example by using `Stream::filter`). Values are fed back after they're delivered to the
+/// observers that were registered before the loop was defined.
+///
+/// # Example
+/// ```
+/// use frappe::stream::StreamLoop;
+/// use frappe::Sink;
+///
+/// let sink = Sink::new();
+/// let retries = StreamLoop::new();
+/// // failed attempts are retried until they succeed
+/// let attempts = sink.stream().merge(&retries.stream().map(|n| *n + 1));
+/// let log = attempts.collect::<Vec<_>>();
+/// retries.define(&attempts.filter(|n| *n < 3));
+///
+/// sink.send(0);
+/// assert_eq!(log.sample(), [0, 1, 2, 3]);
+/// ```
+pub struct StreamLoop<T> {
+    cbs: Arc<Callbacks<T>>,
+    /// The defining stream, and the handle of the feedback registered on it.
+    definition: Mutex<Option<(Stream<T>, CallbackHandle<T>)>>,
+}
+
+impl<T: Clone + Send + 'static> StreamLoop<T> {
+    /// Creates an undefined stream loop.
+    #[inline]
+    pub fn new() -> Self {
+        let cbs = Arc::new(Callbacks::new("loop", &[]));
+        // the loop counts as a sender, since it can be defined later
+        cbs.add_sender();
+        StreamLoop {
+            cbs,
+            definition: Mutex::new(None),
+        }
+    }
+
+    /// Gets the forward declared stream.
+    ///
+    /// It receives the values of the defining stream once the loop is defined.
+    #[inline]
+    pub fn stream(&self) -> Stream<T> {
+        Stream::new(self.cbs.clone(), Source::None)
+    }
+
+    /// Defines the stream loop.
+    ///
+    /// Panics if the loop was already defined.
+    pub fn define(&self, stream: &Stream<T>) {
+        let mut definition = self.definition.lock();
+        assert!(definition.is_none(), "StreamLoop already defined");
+        let feedback = feed_back(stream, &self.cbs);
+        *definition = Some((stream.clone(), feedback));
+    }
+
+    /// Checks if the loop was defined.
+    #[inline]
+    pub fn is_defined(&self) -> bool {
+        self.definition.lock().is_some()
+    }
+}
+
+impl<T: Clone + Send + 'static> Default for StreamLoop<T> {
+    /// Creates an undefined stream loop.
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for StreamLoop<T> {
+    /// Removes the feedback and closes the forward declared stream.
+    fn drop(&mut self) {
+        if let Some((stream, feedback)) = self.definition.lock().take() {
+            stream.cbs.remove(feedback);
+        }
+        self.cbs.remove_sender();
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for StreamLoop<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamLoop")
+            .field("cbs", &self.cbs)
+            .field("defined", &self.definition.lock().is_some())
+            .finish()
+    }
+}
+
 #[cfg(test)]
+#[allow(clippy::needless_borrows_for_generic_args)]
 mod tests {
     use super::*;
     use std::sync::mpsc;
@@ -872,7 +1633,7 @@ mod tests {
     #[test]
     fn stream_scan_n() {
         let sink = Sink::new();
-        let stream = sink.stream().scan_n(std::i32::MIN, |a, n, sender| {
+        let stream = sink.stream().scan_n(i32::MIN, |a, n, sender| {
             let n = *n;
             if n > a {
                 sender.send(n);
@@ -910,7 +1671,7 @@ mod tests {
         assert_eq!(sink.cbs.len(), 0);
     }
 
-    #[cfg(feature = "crossbeam-utils")]
+    #[cfg(all(feature = "crossbeam-utils", not(feature = "rayon")))]
     #[test]
     fn stream_send_parallel() {
         use std::thread;
@@ -936,6 +1697,159 @@ mod tests {
         assert_eq!(result.sample(), 75);
     }
 
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn stream_send_parallel_pool() {
+        use std::thread;
+        use std::time::{Duration, Instant};
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap();
+        // each callback waits until the other one is running too
+        let running = Arc::new(AtomicUsize::new(0));
+        let together = Arc::new(AtomicUsize::new(0));
+        let together_ = together.clone();
+        let wait_other = move || {
+            running.fetch_add(1, Ordering::SeqCst);
+            let t = Instant::now();
+            while running.load(Ordering::SeqCst) & 1 == 1 && t.elapsed() < Duration::from_secs(5) {
+                thread::yield_now();
+            }
+            if running.load(Ordering::SeqCst) & 1 == 0 {
+                together_.fetch_add(1, Ordering::SeqCst);
+            }
+        };
+        let sink = Sink::new();
+        let s1 = sink.stream().map({
+            let wait_other = wait_other.clone();
+            move |x| {
+                wait_other();
+                *x + 1
+            }
+        });
+        let s2 = sink.stream().map(move |x| {
+            wait_other();
+            *x * 2
+        });
+        let result = s1.merge(&s2).fold(0, |a, n| a + *n);
+
+        pool.install(|| sink.send_parallel(&10));
+        assert_eq!(together.load(Ordering::SeqCst), 2);
+        assert_eq!(result.sample(), 31);
+        pool.install(|| {
+            sink.send_parallel(&1);
+            sink.send_parallel(&13);
+        });
+        assert_eq!(result.sample(), 75);
+    }
+
+    #[cfg(any(feature = "crossbeam-utils", feature = "rayon"))]
+    #[test]
+    fn stream_send_parallel_chunked() {
+        let sink = Sink::new();
+        let results: Vec<_> = (0..7)
+            .map(|i| sink.stream().map(move |x| *x + i).fold(0, |a, n| a + *n))
+            .collect();
+
+        sink.send_parallel_with(&10, Chunking::Fixed(3));
+        sink.send_parallel_with(&20, Chunking::Auto);
+        sink.send_parallel_with(&30, Chunking::Fixed(0));
+
+        for (i, result) in results.iter().enumerate() {
+            assert_eq!(result.sample(), 60 + 3 * i as i32);
+        }
+    }
+
+    #[cfg(any(feature = "crossbeam-utils", feature = "rayon"))]
+    #[test]
+    fn stream_send_parallel_cleanup() {
+        let sink = Sink::new();
+        sink.stream().observe(|x| *x < 10);
+        sink.stream().observe(|x| *x < 20);
+        sink.stream().observe(|_| ());
+        let mapped = sink.stream().map(|x| *x + 1);
+        assert_eq!(sink.cbs.len(), 4);
+
+        sink.send_parallel(&5);
+        assert_eq!(sink.cbs.len(), 4);
+
+        drop(mapped);
+        sink.send_parallel(&6);
+        assert_eq!(sink.cbs.len(), 3);
+
+        sink.send_parallel_with(&15, Chunking::Auto);
+        assert_eq!(sink.cbs.len(), 2);
+
+        sink.send_parallel_with(&25, Chunking::Fixed(2));
+        assert_eq!(sink.cbs.len(), 1);
+
+        sink.send_parallel(&30);
+        assert_eq!(sink.cbs.len(), 1);
+    }
+
+    #[test]
+    fn stream_recursive_send() {
+        let sink = Sink::new();
+        let sink_ = sink.clone();
+        let rx = sink.stream().as_sync_channel(10);
+        sink.stream().observe(move |x| {
+            if *x > 0 {
+                sink_.send(*x - 1)
+            }
+        });
+
+        sink.send(3);
+
+        let result: Vec<_> = rx.try_iter().collect();
+        assert_eq!(result, [3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn stream_recursive_subscribe() {
+        let sink = Sink::new();
+        let stream = sink.stream();
+        let stream_ = stream.clone();
+        let (tx, rx) = mpsc::sync_channel(10);
+        let tx = Mutex::new(tx);
+        // every event creates a new observer on the same stream
+        stream.observe(move |x| {
+            let tx = tx.lock().clone();
+            let id = *x;
+            stream_.observe(move |y| tx.send((id, *y)).is_ok());
+        });
+
+        sink.send(1);
+        assert_eq!(rx.try_recv(), Err(mpsc::TryRecvError::Empty));
+        assert_eq!(sink.cbs.len(), 2);
+
+        sink.send(2);
+        assert_eq!(rx.try_recv(), Ok((1, 2)));
+        assert_eq!(rx.try_recv(), Err(mpsc::TryRecvError::Empty));
+        assert_eq!(sink.cbs.len(), 3);
+    }
+
+    #[test]
+    fn stream_switch_same_source() {
+        let sink = Sink::new();
+        let stream = sink.stream();
+        let stream_ = stream.clone();
+        // switches to the same stream every time it receives a zero
+        let switched = stream
+            .filter(|x| *x == 0)
+            .map(move |_| stream_.clone())
+            .switch();
+        let rx = switched.as_sync_channel(10);
+
+        sink.send(0);
+        assert_eq!(rx.try_recv(), Err(mpsc::TryRecvError::Empty));
+
+        sink.feed(vec![1, 2, 0, 3]);
+        let result: Vec<_> = rx.try_iter().collect();
+        assert_eq!(result, [1, 2, 3]);
+    }
+
     #[test]
     fn stream_zip() {
         use std::sync::mpsc::TryRecvError::Empty;
@@ -1047,4 +1961,381 @@ mod tests {
 
         pool.run();
     }
+
+    #[test]
+    fn stream_stats() {
+        let sink = Sink::new();
+        let stream = sink.stream();
+        let odd = stream.filter(|a| a % 2 != 0);
+        let first = odd.element_at(0);
+        let _sum = odd.fold(0, |a, n| a + *n);
+        stream.observe(|_| ());
+
+        sink.feed(1..=6);
+
+        let stats = stream.stats();
+        assert_eq!(stats.events_in, 6);
+        assert_eq!(stats.events_out, 12);
+        assert_eq!(odd.stats().events_in, 3);
+        // element_at drops it's callback after the first value
+        assert_eq!(odd.stats().events_out, 3 + 1);
+        assert_eq!(odd.stats().dropped, 1);
+        assert_eq!(first.stats().events_in, 1);
+    }
+
+    #[test]
+    fn stream_hold_atomic() {
+        let sink = Sink::new();
+        let last = sink.stream().hold_atomic(0u64);
+        let count = sink.stream().fold_atomic(0usize, |n, _| n + 1);
+        let half = sink.stream().map(|x| *x as f64 * 0.5);
+        let sum = half.fold_atomic(0.0, |a, x| a + x);
+        let big = sink.stream().map(|x| vec![*x; 100]).hold_arc(vec![]);
+        assert!(big.sample().is_empty());
+
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let sink = sink.clone();
+                std::thread::spawn(move || sink.feed(i * 10 + 1..=i * 10 + 10))
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        assert_eq!(count.sample(), 40);
+        assert_eq!(sum.sample(), 410.0);
+        assert_eq!(last.sample() % 10, 0);
+        let big = big.sample();
+        assert_eq!(big.len(), 100);
+        assert_eq!(big[0] % 10, 0);
+    }
+
+    #[test]
+    fn stream_loop() {
+        let sink = Sink::new();
+        let lp = StreamLoop::new();
+        let input = sink.stream().merge(&lp.stream().map(|n| *n * 2));
+        let log = input.collect::<Vec<_>>();
+        assert!(!lp.is_defined());
+        lp.define(&input.filter(|n| *n < 10));
+        assert!(lp.is_defined());
+
+        sink.send(1);
+        assert_eq!(log.sample(), [1, 2, 4, 8, 16]);
+
+        // the loop owns the feedback, so dropping it breaks the cycle
+        drop(lp);
+        sink.send(3);
+        assert_eq!(log.sample(), [1, 2, 4, 8, 16, 3]);
+    }
+
+    #[test]
+    fn stream_loop_drop_shared() {
+        let sink = Sink::new();
+        let lp = StreamLoop::new();
+        let input = sink.stream().merge(&lp.stream().map(|n| *n * 2));
+        let log = input.collect::<Vec<_>>();
+        // a copy of the defining stream outlives the loop
+        let definition = input.filter(|n| *n < 10);
+        lp.define(&definition);
+
+        sink.send(1);
+        assert_eq!(log.sample(), [1, 2, 4, 8, 16]);
+
+        drop(lp);
+        sink.send(3);
+        assert_eq!(log.sample(), [1, 2, 4, 8, 16, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "already defined")]
+    fn stream_loop_redefine() {
+        let lp = StreamLoop::<i32>::new();
+        lp.define(&Stream::never());
+        lp.define(&Stream::never());
+    }
+
+    #[test]
+    fn stream_concurrent_observe() {
+        use std::sync::Barrier;
+        use std::thread;
+
+        for _ in 0..100 {
+            let sink = Sink::new();
+            let count = Arc::new(AtomicUsize::new(0));
+            let barrier = Arc::new(Barrier::new(2));
+            let handles = (0..2)
+                .map(|_| {
+                    let stream = sink.stream();
+                    let count = count.clone();
+                    let barrier = barrier.clone();
+                    thread::spawn(move || {
+                        barrier.wait();
+                        stream.observe(move |_| {
+                            count.fetch_add(1, Ordering::SeqCst);
+                        });
+                    })
+                })
+                .collect::<Vec<_>>();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+            // both observers must receive the first value
+            sink.send(());
+            assert_eq!(count.load(Ordering::SeqCst), 2);
+        }
+    }
+
+    #[test]
+    fn stream_cyclic() {
+        let sink = Sink::new();
+        let counter =
+            Stream::cyclic(|s| s.filter(|n| *n < 5).map(|n| *n + 1).merge(&sink.stream()));
+        let log = counter.collect::<Vec<_>>();
+        let source = Arc::downgrade(&sink.cbs);
+
+        sink.send(0);
+        sink.send(3);
+        assert_eq!(log.sample(), [0, 1, 2, 3, 4, 5, 3, 4, 5]);
+
+        // nothing is kept alive by the cycle
+        let weak = Arc::downgrade(&counter.cbs);
+        drop((counter, log, sink));
+        assert!(weak.upgrade().is_none());
+        assert!(source.upgrade().is_none());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn stream_tokio_channels() {
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        let sink = Sink::from_mpsc(rx);
+        let doubled = sink.stream().map(|n| *n * 2);
+        let mut rx = doubled.to_broadcast(4);
+
+        tx.send(1).await.unwrap();
+        tx.send(2).await.unwrap();
+        assert_eq!(rx.recv().await.unwrap(), 2);
+        assert_eq!(rx.recv().await.unwrap(), 4);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn stream_to_sync_channel() {
+        let sink = Sink::new();
+        let newest = sink.stream().to_sync_channel(2, OverflowPolicy::DropNewest);
+        let oldest = sink.stream().to_sync_channel(2, OverflowPolicy::DropOldest);
+        let dropped = sink.stream().to_sync_channel(2, OverflowPolicy::DropNewest);
+        drop(dropped);
+
+        sink.feed(0..5);
+        assert_eq!(newest.try_iter().collect::<Vec<_>>(), [0, 1]);
+        // the forwarding thread holds the first value, and the queue the latest one
+        assert_eq!(oldest.iter().take(2).collect::<Vec<_>>(), [0, 4]);
+        assert!(oldest.try_recv().is_err());
+        // the disconnected channel was unregistered
+        assert_eq!(sink.cbs.len(), 2);
+
+        let coalesce = sink
+            .stream()
+            .to_sync_channel(3, OverflowPolicy::CoalesceLatest);
+        sink.feed(0..5);
+        assert_eq!(coalesce.iter().take(3).collect::<Vec<_>>(), [0, 1, 4]);
+        // the thread notices the dropped receiver at the next value, and then the observer is
+        // removed at the value after that
+        drop(coalesce);
+        for i in 5..100 {
+            if sink.cbs.len() == 2 {
+                break;
+            }
+            sink.send(i);
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(sink.cbs.len(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn stream_buffered() {
+        let sink = Sink::new();
+        let (buffered, depth) = sink.stream().buffered(2, OverflowPolicy::DropOldest);
+        let (started_tx, started_rx) = mpsc::channel();
+        let (gate_tx, gate_rx) = mpsc::channel();
+        let gate_rx = Mutex::new(gate_rx);
+        // a slow observer that waits for permission to finish each value
+        buffered.observe(move |n| {
+            started_tx.send(*n).unwrap();
+            gate_rx.lock().recv().unwrap()
+        });
+
+        sink.send(0);
+        assert_eq!(started_rx.recv(), Ok(0));
+        // the sender isn't blocked by the observer
+        sink.feed(1..5);
+        assert_eq!(depth.sample(), 2);
+
+        for _ in 0..3 {
+            gate_tx.send(()).unwrap();
+        }
+        assert_eq!(started_rx.recv(), Ok(3));
+        assert_eq!(started_rx.recv(), Ok(4));
+        assert_eq!(depth.sample(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn stream_map_async() {
+        use crate::futures::BoxFuture;
+        use futures::channel::oneshot;
+        use futures::executor::block_on;
+        use std::collections::HashMap;
+        use std::pin::Pin;
+
+        type Gates = Arc<Mutex<HashMap<i32, oneshot::Sender<()>>>>;
+        type Job = Box<dyn Fn(i32) -> Pin<Box<dyn Future<Output = i32> + Send>> + Send + Sync>;
+
+        // every future waits until the test releases it
+        fn gated() -> (Job, Gates, mpsc::Receiver<i32>) {
+            let gates = Gates::default();
+            let (started_tx, started_rx) = mpsc::channel();
+            let gates_ = gates.clone();
+            let job = move |n| {
+                let (tx, rx) = oneshot::channel();
+                gates_.lock().insert(n, tx);
+                started_tx.send(n).unwrap();
+                Box::pin(async move {
+                    let _ = rx.await;
+                    n * 10
+                }) as Pin<Box<dyn Future<Output = i32> + Send>>
+            };
+            (Box::new(job), gates, started_rx)
+        }
+        fn release(gates: &Gates, n: i32) {
+            let _ = gates.lock().remove(&n).unwrap().send(());
+        }
+        let spawn = |fut: BoxFuture| {
+            thread::spawn(move || block_on(fut));
+        };
+        let timeout = std::time::Duration::from_millis(50);
+
+        let sink = Sink::new();
+        let (job, gates, started) = gated();
+        let ordered = sink.stream().map_async(job, Concurrency::Ordered(2), spawn);
+        let rx = ordered.as_sync_channel(10);
+        sink.feed(vec![1, 2, 3]);
+        assert_eq!((started.recv(), started.recv()), (Ok(1), Ok(2)));
+        // the third value waits for a free slot
+        assert!(started.recv_timeout(timeout).is_err());
+        release(&gates, 2);
+        assert_eq!(started.recv(), Ok(3));
+        release(&gates, 3);
+        assert!(rx.recv_timeout(timeout).is_err());
+        release(&gates, 1);
+        assert_eq!(rx.iter().take(3).collect::<Vec<_>>(), [10, 20, 30]);
+
+        let sink = Sink::new();
+        let (job, gates, _started) = gated();
+        let unordered = sink
+            .stream()
+            .map_async(job, Concurrency::Unordered(2), spawn);
+        let rx = unordered.as_sync_channel(10);
+        sink.feed(vec![1, 2]);
+        release(&gates, 2);
+        assert_eq!(rx.recv(), Ok(20));
+        release(&gates, 1);
+        assert_eq!(rx.recv(), Ok(10));
+
+        let sink = Sink::new();
+        let (job, gates, started) = gated();
+        let latest = sink.stream().map_async(job, Concurrency::LatestOnly, spawn);
+        let rx = latest.as_sync_channel(10);
+        sink.feed(vec![1, 2]);
+        assert_eq!((started.recv(), started.recv()), (Ok(1), Ok(2)));
+        // the stale future is dropped without being released
+        let stale = gates.lock().remove(&1).unwrap();
+        for _ in 0..100 {
+            if stale.is_canceled() {
+                break;
+            }
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert!(stale.is_canceled());
+        release(&gates, 2);
+        assert_eq!(rx.recv(), Ok(20));
+        assert!(rx.recv_timeout(timeout).is_err());
+
+        // a panicking future frees it's slot and doesn't stall the ones after it
+        let sink = Sink::new();
+        let ordered = sink.stream().map_async(
+            |n: i32| async move {
+                if n == 2 {
+                    panic!("bad value");
+                }
+                n * 10
+            },
+            Concurrency::Ordered(1),
+            spawn,
+        );
+        let rx = ordered.as_sync_channel(10);
+        sink.feed(vec![1, 2, 3]);
+        assert_eq!(rx.iter().take(2).collect::<Vec<_>>(), [10, 30]);
+    }
+
+    #[cfg(feature = "crossbeam-channel")]
+    #[test]
+    fn stream_to_crossbeam() {
+        let sink = Sink::new();
+        let rx = sink.stream().to_crossbeam(2, OverflowPolicy::Block);
+        let newest = sink.stream().to_crossbeam(2, OverflowPolicy::DropNewest);
+
+        sink.feed(vec![1, 2]);
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), [1, 2]);
+        sink.send(3);
+        assert_eq!(newest.try_iter().collect::<Vec<_>>(), [1, 2]);
+        assert_eq!(rx.recv(), Ok(3));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn sink_pump_from() {
+        let (tx, rx) = mpsc::channel();
+        let sink = Sink::new();
+        let sum = sink.stream().fold(0, |a, n| a + *n);
+        let handle = sink.pump_from(rx, 4, OverflowPolicy::Block);
+
+        for i in 1..=10 {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        handle.join().unwrap();
+        assert_eq!(sum.sample(), 55);
+
+        // a slow observer only gets the oldest and the latest values
+        let (tx, rx) = mpsc::channel();
+        let sink = Sink::new();
+        let (gate_tx, gate_rx) = mpsc::channel::<()>();
+        let gate_rx = Mutex::new(gate_rx);
+        let values = sink
+            .stream()
+            .inspect(move |_| gate_rx.lock().recv().unwrap())
+            .collect::<Vec<_>>();
+        // signals when the channel was read to the end
+        let (done_tx, done_rx) = mpsc::channel();
+        let rx = rx.into_iter().chain(std::iter::from_fn(move || {
+            let _ = done_tx.send(());
+            None
+        }));
+        let handle = sink.pump_from(rx, 1, OverflowPolicy::DropOldest);
+        for i in 1..=5 {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        done_rx.recv().unwrap();
+        for _ in 0..5 {
+            let _ = gate_tx.send(());
+        }
+        handle.join().unwrap();
+        assert_eq!(values.sample(), [1, 5]);
+    }
 }
@@ -3,13 +3,35 @@
 #[cfg(feature = "parking_lot")]
 pub use parking_lot::{Mutex, RwLock};
 
-#[cfg(not(feature = "parking_lot"))]
+#[cfg(all(feature = "std", not(feature = "parking_lot")))]
 pub use self::wrapper::{Mutex, RwLock};
 
-#[cfg(not(feature = "parking_lot"))]
+#[cfg(not(feature = "std"))]
+pub use spin::{Mutex, RwLock};
+
+#[cfg(all(feature = "std", not(feature = "parking_lot")))]
 #[allow(dead_code)]
 mod wrapper {
-    use std::sync::{MutexGuard, RwLockReadGuard, RwLockWriteGuard};
+    //! Wrappers that ignore poisoning, to match the API of the other lock implementations.
+
+    use std::sync::{LockResult, TryLockError, TryLockResult};
+    use std::sync::{MutexGuard, PoisonError, RwLockReadGuard, RwLockWriteGuard};
+
+    /// Takes the guard even if the lock is poisoned.
+    #[inline]
+    fn recover<G>(res: LockResult<G>) -> G {
+        res.unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Takes the guard even if the lock is poisoned, or `None` if it would block.
+    #[inline]
+    fn try_recover<G>(res: TryLockResult<G>) -> Option<G> {
+        match res {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::Poisoned(err)) => Some(err.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
 
     #[derive(Debug, Default)]
     pub struct Mutex<T>(std::sync::Mutex<T>);
@@ -21,13 +43,13 @@ mod wrapper {
         }
 
         #[inline]
-        pub fn lock(&self) -> MutexGuard<T> {
-            self.0.lock().unwrap()
+        pub fn lock(&self) -> MutexGuard<'_, T> {
+            recover(self.0.lock())
         }
 
         #[inline]
-        pub fn try_lock(&self) -> Option<MutexGuard<T>> {
-            self.0.try_lock().ok()
+        pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+            try_recover(self.0.try_lock())
         }
     }
 
@@ -41,23 +63,23 @@ mod wrapper {
         }
 
         #[inline]
-        pub fn read(&self) -> RwLockReadGuard<T> {
-            self.0.read().unwrap()
+        pub fn read(&self) -> RwLockReadGuard<'_, T> {
+            recover(self.0.read())
         }
 
         #[inline]
-        pub fn write(&self) -> RwLockWriteGuard<T> {
-            self.0.write().unwrap()
+        pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+            recover(self.0.write())
         }
 
         #[inline]
-        pub fn try_read(&self) -> Option<RwLockReadGuard<T>> {
-            self.0.try_read().ok()
+        pub fn try_read(&self) -> Option<RwLockReadGuard<'_, T>> {
+            try_recover(self.0.try_read())
         }
 
         #[inline]
-        pub fn try_write(&self) -> Option<RwLockWriteGuard<T>> {
-            self.0.try_write().ok()
+        pub fn try_write(&self) -> Option<RwLockWriteGuard<'_, T>> {
+            try_recover(self.0.try_write())
         }
     }
 }
@@ -0,0 +1,190 @@
+//! Tracing spans for event propagation.
+//!
+//! With the `tracing` feature enabled every value sent into a `Sink` opens a `send` span, and every
+//! stream operation the value passes through opens a nested `hop` span. Without the feature the
+//! spans are empty placeholders that compile to nothing.
+
+use crate::types::NodeInfo;
+#[cfg(feature = "tracing")]
+use crate::types::NodeKind;
+
+#[cfg(feature = "tracing")]
+pub use tracing::Span;
+
+/// Placeholder for `tracing::Span` when the `tracing` feature is disabled.
+#[cfg(not(feature = "tracing"))]
+#[derive(Debug, Clone)]
+pub struct Span;
+
+#[cfg(not(feature = "tracing"))]
+impl Span {
+    /// Gets the current span.
+    #[cfg(any(feature = "crossbeam-utils", feature = "rayon"))]
+    #[inline(always)]
+    pub fn current() -> Self {
+        Span
+    }
+
+    /// Runs a closure inside this span.
+    #[inline(always)]
+    pub fn in_scope<F: FnOnce() -> R, R>(&self, f: F) -> R {
+        f()
+    }
+}
+
+/// Creates the span of a value sent into a sink.
+#[inline]
+pub fn send(node: &NodeInfo, owned: bool) -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        tracing::debug_span!(
+            "send",
+            node = node.id(),
+            name = node.name().as_deref(),
+            value_type = node.value_type(),
+            owned
+        )
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (node, owned);
+        Span
+    }
+}
+
+/// Creates the span of a value passing through a stream operation.
+///
+/// Sinks don't get a `hop` span, since they already have the `send` span.
+#[inline]
+pub fn hop(node: &NodeInfo, owned: bool) -> Span {
+    #[cfg(feature = "tracing")]
+    {
+        if node.kind() == NodeKind::Sink {
+            return Span::none();
+        }
+        tracing::trace_span!(
+            "hop",
+            node = node.id(),
+            operator = node.operator(),
+            name = node.name().as_deref(),
+            value_type = node.value_type(),
+            owned
+        )
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        let _ = (node, owned);
+        Span
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+#[allow(clippy::needless_borrows_for_generic_args)]
+mod tests {
+    use crate::Sink;
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// A recorded span: name, parent index and fields.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct SpanData {
+        name: &'static str,
+        parent: Option<usize>,
+        fields: HashMap<&'static str, String>,
+    }
+
+    impl Visit for SpanData {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            self.fields.insert(field.name(), format!("{:?}", value));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.fields.insert(field.name(), value.to_string());
+        }
+    }
+
+    /// Subscriber that records all the spans in creation order.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        spans: Arc<Mutex<Vec<SpanData>>>,
+        stack: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let mut data = SpanData {
+                name: attrs.metadata().name(),
+                parent: self.stack.lock().unwrap().last().cloned(),
+                fields: HashMap::new(),
+            };
+            attrs.record(&mut data);
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(data);
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, span: &Id) {
+            self.stack
+                .lock()
+                .unwrap()
+                .push(span.into_u64() as usize - 1);
+        }
+
+        fn exit(&self, _: &Id) {
+            self.stack.lock().unwrap().pop();
+        }
+    }
+
+    #[test]
+    fn trace_send() {
+        let recorder = Recorder::default();
+        let sink = Sink::<i32>::new();
+        let s1 = sink.stream().named("input");
+        let s2 = s1.map(|x| *x + 1).named("plus_one");
+        let s3 = s2.filter(|x| *x > 0);
+        let _s4 = s3.map(|x| x.to_string());
+
+        tracing::subscriber::with_default(recorder.clone(), || {
+            sink.send(1);
+            // filtered out after the first hop
+            sink.send(&-5);
+        });
+
+        let spans = recorder.spans.lock().unwrap();
+        let names: Vec<_> = spans.iter().map(|s| s.name).collect();
+        let parents: Vec<_> = spans.iter().map(|s| s.parent).collect();
+        assert_eq!(names, ["send", "hop", "hop", "hop", "send", "hop"]);
+        assert_eq!(parents, [None, Some(0), Some(1), Some(2), None, Some(4)]);
+
+        let send = &spans[0].fields;
+        assert_eq!(send["name"], "input");
+        assert_eq!(send["value_type"], "i32");
+        assert_eq!(send["owned"], "true");
+        let map = &spans[1].fields;
+        assert_eq!(map["operator"], "map");
+        assert_eq!(map["name"], "plus_one");
+        assert_eq!(map["owned"], "true");
+        let filter = &spans[2].fields;
+        assert_eq!(filter["operator"], "filter");
+        assert!(!filter.contains_key("name"));
+        let to_string = &spans[3].fields;
+        assert_eq!(to_string["value_type"], "alloc::string::String");
+
+        assert_eq!(spans[4].fields["owned"], "false");
+        assert_eq!(spans[5].fields["owned"], "true");
+    }
+}
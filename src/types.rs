@@ -2,14 +2,28 @@
 
 #[cfg(feature = "either")]
 pub use either::Either;
-pub use maybe_owned::MaybeOwned;
+mod maybe_owned;
+pub use crate::types::maybe_owned::MaybeOwned;
 
 mod callbacks;
-pub(crate) use crate::types::callbacks::Callbacks;
+pub(crate) use crate::types::callbacks::{CallbackHandle, Callbacks, Watcher};
 
 mod storage;
 pub(crate) use crate::types::storage::Storage;
 
+mod atomic;
+pub use crate::types::atomic::AtomicValue;
+pub(crate) use crate::types::atomic::{ArcStorage, AtomicStorage};
+
+#[cfg(feature = "std")]
+mod queue;
+#[cfg(feature = "std")]
+pub(crate) use crate::types::queue::{BoundedQueue, QueueWriter};
+
+pub(crate) mod node;
+pub(crate) use crate::types::node::NodeInfo;
+pub use crate::types::node::{LatencyHistogram, NodeKind, NodeStats, LATENCY_BUCKETS};
+
 /// Generic sum type of two elements.
 ///
 /// It's used to provide generics over the `Option`/`Result`/`Either` types
@@ -142,6 +156,7 @@ impl<L, R> SumType2 for Either<L, R> {
 }
 
 /// Determines if the `Stream::observe` callback should be dropped or not.
+#[allow(clippy::wrong_self_convention)]
 pub trait ObserveResult {
     /// If it returns `true` the callback is kept, otherwise it's dropped.
     fn is_callback_alive(self) -> bool;
@@ -178,3 +193,46 @@ impl<T, E> ObserveResult for Result<T, E> {
         self.is_ok()
     }
 }
+
+/// Determines what happens when a value is sent into a full channel or queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// The sender waits until there is space. This is the default.
+    #[default]
+    Block,
+    /// The new value is discarded.
+    DropNewest,
+    /// The oldest value is discarded to make space for the new one.
+    DropOldest,
+    /// The new value replaces the last one waiting, so the receiver always gets the latest.
+    CoalesceLatest,
+}
+
+/// Determines how `Sink::send_parallel_with` splits the callbacks into parallel jobs.
+#[cfg(any(feature = "crossbeam-utils", feature = "rayon"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Chunking {
+    /// Each callback runs as a separate job. This is the default.
+    ///
+    /// This is the best choice when the callbacks can block for a long time.
+    #[default]
+    PerCallback,
+    /// Callbacks are grouped in jobs of the specified size.
+    Fixed(usize),
+    /// Callbacks are split evenly across the available worker threads.
+    ///
+    /// This minimizes the overhead when the callbacks are cheap to run.
+    Auto,
+}
+
+#[cfg(any(feature = "crossbeam-utils", feature = "rayon"))]
+impl Chunking {
+    /// Computes the amount of callbacks that go on each job.
+    pub(crate) fn chunk_size(self, n_callbacks: usize, n_threads: usize) -> usize {
+        match self {
+            Chunking::PerCallback => 1,
+            Chunking::Fixed(size) => size.max(1),
+            Chunking::Auto => n_callbacks.div_ceil(n_threads.max(1)).max(1),
+        }
+    }
+}
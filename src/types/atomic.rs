@@ -0,0 +1,233 @@
+//! Lock-free storage cells used by Signal.
+
+use crate::types::{NodeInfo, NodeKind};
+use alloc::sync::Arc;
+use core::sync::atomic::Ordering::{AcqRel, Acquire, Release};
+use core::sync::atomic::{AtomicBool, AtomicI16, AtomicI32, AtomicI8, AtomicIsize};
+#[cfg(target_has_atomic = "64")]
+use core::sync::atomic::{AtomicI64, AtomicU64};
+use core::sync::atomic::{AtomicU16, AtomicU32, AtomicU8, AtomicUsize};
+
+#[cfg(not(feature = "arc-swap"))]
+use crate::sync::RwLock;
+#[cfg(feature = "arc-swap")]
+use arc_swap::ArcSwap;
+
+/// A value that can be stored in an atomic.
+///
+/// It's implemented for `bool`, the integer types, and the float types (stored as their bit
+/// representation). Signals that hold these values don't need a lock to be sampled.
+pub trait AtomicValue: Copy + Send + Sync + 'static {
+    /// The atomic type that stores the value.
+    type Atomic: Send + Sync;
+
+    /// Creates an atomic with an initial value.
+    fn new_atomic(val: Self) -> Self::Atomic;
+
+    /// Loads the value.
+    fn load(atomic: &Self::Atomic) -> Self;
+
+    /// Stores a new value.
+    fn store(atomic: &Self::Atomic, val: Self);
+
+    /// Stores `new` if the current value is `current`, returning the previous value.
+    fn compare_exchange_weak(atomic: &Self::Atomic, current: Self, new: Self)
+        -> Result<Self, Self>;
+}
+
+macro_rules! impl_atomic_value {
+    ($($t:ty => $atomic:ty),*) => {$(
+        impl AtomicValue for $t {
+            type Atomic = $atomic;
+
+            #[inline]
+            fn new_atomic(val: Self) -> Self::Atomic {
+                <$atomic>::new(val)
+            }
+
+            #[inline]
+            fn load(atomic: &Self::Atomic) -> Self {
+                atomic.load(Acquire)
+            }
+
+            #[inline]
+            fn store(atomic: &Self::Atomic, val: Self) {
+                atomic.store(val, Release)
+            }
+
+            #[inline]
+            fn compare_exchange_weak(
+                atomic: &Self::Atomic,
+                current: Self,
+                new: Self,
+            ) -> Result<Self, Self> {
+                atomic.compare_exchange_weak(current, new, AcqRel, Acquire)
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_atomic_float {
+    ($($t:ty => $atomic:ty),*) => {$(
+        impl AtomicValue for $t {
+            type Atomic = $atomic;
+
+            #[inline]
+            fn new_atomic(val: Self) -> Self::Atomic {
+                <$atomic>::new(val.to_bits())
+            }
+
+            #[inline]
+            fn load(atomic: &Self::Atomic) -> Self {
+                <$t>::from_bits(atomic.load(Acquire))
+            }
+
+            #[inline]
+            fn store(atomic: &Self::Atomic, val: Self) {
+                atomic.store(val.to_bits(), Release)
+            }
+
+            #[inline]
+            fn compare_exchange_weak(
+                atomic: &Self::Atomic,
+                current: Self,
+                new: Self,
+            ) -> Result<Self, Self> {
+                atomic
+                    .compare_exchange_weak(current.to_bits(), new.to_bits(), AcqRel, Acquire)
+                    .map(<$t>::from_bits)
+                    .map_err(<$t>::from_bits)
+            }
+        }
+    )*};
+}
+
+impl_atomic_value!(
+    bool => AtomicBool,
+    u8 => AtomicU8, u16 => AtomicU16, u32 => AtomicU32, usize => AtomicUsize,
+    i8 => AtomicI8, i16 => AtomicI16, i32 => AtomicI32, isize => AtomicIsize
+);
+#[cfg(target_has_atomic = "64")]
+impl_atomic_value!(u64 => AtomicU64, i64 => AtomicI64);
+
+impl_atomic_float!(f32 => AtomicU32);
+#[cfg(target_has_atomic = "64")]
+impl_atomic_float!(f64 => AtomicU64);
+
+/// Storage cell for signal values that fit in an atomic.
+pub struct AtomicStorage<T: AtomicValue> {
+    val: T::Atomic,
+    /// Keeps the storage registered in the event graph.
+    #[allow(dead_code)]
+    node: Arc<NodeInfo>,
+}
+
+impl<T: AtomicValue> AtomicStorage<T> {
+    /// Creates a storage that is visible in the event graph.
+    pub fn with_node(val: T, operator: &'static str, parent: usize) -> Self {
+        AtomicStorage {
+            val: T::new_atomic(val),
+            node: NodeInfo::new::<T>(NodeKind::Storage, operator, &[parent]),
+        }
+    }
+
+    /// Gets the value.
+    #[inline]
+    pub fn get(&self) -> T {
+        T::load(&self.val)
+    }
+
+    /// Sets the value.
+    #[inline]
+    pub fn set(&self, val: T) {
+        T::store(&self.val, val)
+    }
+
+    /// Replaces the value with the result of a function.
+    ///
+    /// The function can be called multiple times if other threads change the value at the same
+    /// time.
+    pub fn update<F>(&self, f: F)
+    where
+        F: Fn(T) -> T,
+    {
+        let mut current = self.get();
+        while let Err(actual) = T::compare_exchange_weak(&self.val, current, f(current)) {
+            current = actual;
+        }
+    }
+}
+
+/// Storage cell for shared immutable values.
+///
+/// Sampling only clones the `Arc`. With the `arc-swap` feature enabled it's lock-free.
+pub struct ArcStorage<T> {
+    #[cfg(feature = "arc-swap")]
+    val: ArcSwap<T>,
+    #[cfg(not(feature = "arc-swap"))]
+    val: RwLock<Arc<T>>,
+    /// Keeps the storage registered in the event graph.
+    #[allow(dead_code)]
+    node: Arc<NodeInfo>,
+}
+
+impl<T: 'static> ArcStorage<T> {
+    /// Creates a storage that is visible in the event graph.
+    pub fn with_node(val: T, operator: &'static str, parent: usize) -> Self {
+        ArcStorage {
+            #[cfg(feature = "arc-swap")]
+            val: ArcSwap::from_pointee(val),
+            #[cfg(not(feature = "arc-swap"))]
+            val: RwLock::new(Arc::new(val)),
+            node: NodeInfo::new::<T>(NodeKind::Storage, operator, &[parent]),
+        }
+    }
+
+    /// Gets a reference to the value.
+    #[inline]
+    pub fn get(&self) -> Arc<T> {
+        #[cfg(feature = "arc-swap")]
+        return self.val.load_full();
+        #[cfg(not(feature = "arc-swap"))]
+        return self.val.read().clone();
+    }
+
+    /// Sets the value.
+    #[inline]
+    pub fn set(&self, val: T) {
+        #[cfg(feature = "arc-swap")]
+        self.val.store(Arc::new(val));
+        #[cfg(not(feature = "arc-swap"))]
+        {
+            *self.val.write() = Arc::new(val);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atomic_storage() {
+        let st = AtomicStorage::with_node(1u8, "test", 0);
+        st.update(|n| n + 2);
+        assert_eq!(st.get(), 3);
+        st.set(100);
+        assert_eq!(st.get(), 100);
+
+        let st = AtomicStorage::with_node(-1.5f32, "test", 0);
+        st.update(|x| x * 2.0);
+        assert_eq!(st.get(), -3.0);
+
+        let st = AtomicStorage::with_node(false, "test", 0);
+        st.update(|b| !b);
+        assert!(st.get());
+
+        let st = ArcStorage::with_node([0u64; 64], "test", 0);
+        let old = st.get();
+        st.set([1; 64]);
+        assert_eq!(old[0], 0);
+        assert_eq!(st.get()[63], 1);
+    }
+}
@@ -1,36 +1,77 @@
 //! Callback container for Stream.
+//!
+//! The callback list is reentrant: a callback can send values or add new callbacks to the same
+//! list it's being called from. Callbacks added while the list is being dispatched are queued and
+//! then appended after the dispatch finishes, so they won't receive the value being currently sent.
+//!
+//! With the `arc-swap` feature enabled the list is stored as a copy-on-write vector, so sending
+//! values is lock-free at the cost of copying the list every time a callback is added or removed.
 
+use crate::trace;
+use crate::types::{MaybeOwned, NodeInfo, NodeKind};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::sync::Mutex;
+#[cfg(not(feature = "arc-swap"))]
 use crate::sync::RwLock;
-use maybe_owned::MaybeOwned;
-use std::fmt;
-use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "arc-swap")]
+use arc_swap::ArcSwap;
 
-#[cfg(feature = "crossbeam-utils")]
+#[cfg(any(feature = "crossbeam-utils", feature = "rayon"))]
+use crate::types::Chunking;
+#[cfg(all(feature = "crossbeam-utils", not(feature = "rayon")))]
 use crossbeam_utils::thread;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// The closure type stored by `FnCell`.
+type CallbackFn<T> = dyn Fn(MaybeOwned<'_, T>) -> bool + Send + Sync;
+
+/// The element type of the callback list.
+///
+/// The cells are reference counted so a `CallbackHandle` can find it's cell, and with `arc-swap` so
+/// they can be shared between the copies of the list.
+type Cell<T> = Arc<FnCell<CallbackFn<T>>>;
 
 /// Function that becomes uncallable after it returns false.
 ///
 /// Callbacks use a `MaybeOwned<T>` argument so we can choose at runtime if we will send a ref or an owned value.
-struct FnCell<T> {
-    f: Box<dyn Fn(MaybeOwned<'_, T>) -> bool + Send + Sync>,
+struct FnCell<F: ?Sized> {
     alive: AtomicBool,
+    f: F,
 }
 
-impl<T> FnCell<T> {
+impl<T> FnCell<CallbackFn<T>> {
     /// Creates a new `FnCell` from the supplied closure.
-    fn new<F>(f: F) -> Self
+    fn new<F>(f: F) -> Arc<Self>
     where
         F: Fn(MaybeOwned<'_, T>) -> bool + Send + Sync + 'static,
     {
-        FnCell {
-            f: Box::new(f),
+        Arc::new(FnCell {
             alive: AtomicBool::new(true),
-        }
+            f,
+        })
     }
 
     /// Calls the stored function and updates it's callable status.
-    fn call(&self, arg: MaybeOwned<'_, T>) -> bool {
+    ///
+    /// The call is recorded on the metrics of `node`.
+    fn call(&self, node: &NodeInfo, arg: MaybeOwned<'_, T>) -> bool {
         if self.alive.load(Ordering::Relaxed) {
+            node.record_out();
+            #[cfg(feature = "std")]
+            let is_alive = match node.latency_start() {
+                Some(start) => {
+                    let is_alive = (self.f)(arg);
+                    node.record_latency(start);
+                    is_alive
+                }
+                None => (self.f)(arg),
+            };
+            #[cfg(not(feature = "std"))]
             let is_alive = (self.f)(arg);
             if !is_alive {
                 self.alive.store(false, Ordering::Relaxed);
@@ -47,71 +88,147 @@ impl<T> FnCell<T> {
     }
 }
 
-impl<T> fmt::Debug for FnCell<T> {
+impl<F: ?Sized> fmt::Debug for FnCell<F> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
             "FnCell {{ f: Fn@{:p}, alive: {:?} }}",
-            self.f, self.alive
+            &self.f, self.alive
         )
     }
 }
 
+/// Identifies a callback so it can be removed from it's list.
+pub struct CallbackHandle<T>(Weak<FnCell<CallbackFn<T>>>);
+
+impl<T> CallbackHandle<T> {
+    /// Marks the callback as dead, so it won't be called again.
+    fn kill(&self) {
+        if let Some(cell) = self.0.upgrade() {
+            cell.alive.store(false, Ordering::Relaxed);
+        }
+    }
+}
+
+impl<T> fmt::Debug for CallbackHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CallbackHandle({:?})", self.0.upgrade())
+    }
+}
+
+/// Something waiting for a callback list to lose it's senders.
+pub trait Watcher: Send + Sync {
+    /// Called after the last sender is gone.
+    fn closed(&self);
+}
+
+/// Counts the handles that can send values into a callback list.
+#[derive(Default)]
+struct Senders {
+    count: AtomicUsize,
+    watchers: Mutex<Vec<Weak<dyn Watcher>>>,
+}
+
+impl fmt::Debug for Senders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Senders")
+            .field("count", &self.count)
+            .finish()
+    }
+}
+
+/// A collection of callbacks.
+#[cfg(not(feature = "arc-swap"))]
+#[derive(Debug)]
+pub struct Callbacks<T> {
+    node: Arc<NodeInfo>,
+    senders: Senders,
+    fs: RwLock<Vec<Cell<T>>>,
+    /// Callbacks added while `fs` was locked.
+    queued: Mutex<Vec<Cell<T>>>,
+    /// Signals that `queued` has callbacks waiting to be appended, or that a callback was removed
+    /// while `fs` was locked.
+    has_queued: AtomicBool,
+}
+
 /// A collection of callbacks.
+#[cfg(feature = "arc-swap")]
 #[derive(Debug)]
 pub struct Callbacks<T> {
-    fs: RwLock<Vec<FnCell<T>>>,
+    node: Arc<NodeInfo>,
+    senders: Senders,
+    fs: ArcSwap<Vec<Cell<T>>>,
 }
 
 impl<T> Callbacks<T> {
-    /// Creates an empty callback list.
-    pub fn new() -> Self {
-        Default::default()
+    /// Creates an empty callback list for the output of a stream operation.
+    pub fn new(operator: &'static str, parents: &[usize]) -> Self {
+        Self::with_node(NodeInfo::new::<T>(NodeKind::Stream, operator, parents))
     }
 
-    /// Adds a new closure to the callback list.
-    pub fn push<F>(&self, cb: F)
-    where
-        F: Fn(MaybeOwned<'_, T>) -> bool + Send + Sync + 'static,
-    {
-        self.fs.write().push(FnCell::new(cb))
+    /// Gets the graph metadata of this callback list.
+    #[inline]
+    pub fn node(&self) -> &NodeInfo {
+        &self.node
+    }
+
+    /// Registers a handle that can send values into this list, like a `Sink`.
+    #[inline]
+    pub fn add_sender(&self) {
+        self.senders.count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Unregisters a sender, and notifies the watchers if it was the last one.
+    ///
+    /// The watchers stay registered, because the senders of a derived stream (like the ones
+    /// given by `Stream::map_n`) can come and go.
+    pub fn remove_sender(&self) {
+        if self.senders.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            let watchers = self
+                .senders
+                .watchers
+                .lock()
+                .iter()
+                .filter_map(Weak::upgrade)
+                .collect::<Vec<_>>();
+            for watcher in watchers {
+                watcher.closed();
+            }
+        }
+    }
+
+    /// Checks if there are handles that can send values into this list.
+    #[inline]
+    pub fn has_senders(&self) -> bool {
+        self.senders.count.load(Ordering::SeqCst) > 0
+    }
+
+    /// Registers a watcher that is notified when the last sender is gone.
+    ///
+    /// The watchers that were dropped are removed at the same time.
+    pub fn watch_senders(&self, watcher: Weak<dyn Watcher>) {
+        let mut watchers = self.senders.watchers.lock();
+        watchers.retain(|w| w.strong_count() > 0);
+        watchers.push(watcher);
     }
 
     /// Sends an owned value.
     ///
     /// This sends a ref to the first N-1 callbacks, and the owned value to the last.
     pub fn call_owned(&self, arg: T) {
-        let fs = self.fs.read();
-        let n = fs.len();
-
-        let mut i = 0;
-        let mut all_alive = true;
-        for _ in 1..n {
-            all_alive &= fs[i].call(MaybeOwned::Borrowed(&arg));
-            i += 1;
-        }
-        if n > 0 {
-            all_alive &= fs[i].call(MaybeOwned::Owned(arg));
-        }
-        drop(fs);
-
-        if !all_alive {
-            self.cleanup();
-        }
+        trace::hop(&self.node, true).in_scope(|| {
+            self.dispatch(|fs| match fs.split_last() {
+                Some((last, rest)) => {
+                    call_all(&self.node, rest, &arg) & last.call(&self.node, MaybeOwned::Owned(arg))
+                }
+                None => true,
+            })
+        })
     }
 
     /// Sends a value by reference.
     pub fn call_ref(&self, arg: &T) {
-        let all_alive = self
-            .fs
-            .read()
-            .iter()
-            .map(|f| f.call(MaybeOwned::Borrowed(arg)))
-            .fold(true, |a, alive| a & alive);
-
-        if !all_alive {
-            self.cleanup();
-        }
+        trace::hop(&self.node, false).in_scope(|| self.dispatch(|fs| call_all(&self.node, fs, arg)))
     }
 
     /// Sends a value.
@@ -126,72 +243,267 @@ impl<T> Callbacks<T> {
         }
     }
 
+    /// Adds a new closure that can be removed with `Callbacks::remove`.
+    pub fn push_removable<F>(&self, cb: F) -> CallbackHandle<T>
+    where
+        F: Fn(MaybeOwned<'_, T>) -> bool + Send + Sync + 'static,
+    {
+        let cell = FnCell::new(cb);
+        let handle = CallbackHandle(Arc::downgrade(&cell));
+        self.push_cell(cell);
+        handle
+    }
+
+    /// Sends a value using the rayon thread pool.
+    ///
+    /// The callbacks are split into jobs according to `chunking` and executed on the current
+    /// rayon pool, then it waits for all of them to finish.
+    #[cfg(feature = "rayon")]
+    pub fn call_parallel(&self, arg: &T, chunking: Chunking)
+    where
+        T: Sync,
+    {
+        trace::hop(&self.node, false).in_scope(|| {
+            self.dispatch(|fs| {
+                // 0 or 1 callbacks, just run them on this thread
+                if fs.len() < 2 {
+                    return call_all(&self.node, fs, arg);
+                }
+                let chunk_size = chunking.chunk_size(fs.len(), rayon::current_num_threads());
+                // the jobs continue the current span on the worker threads
+                let span = trace::Span::current();
+                fs.par_chunks(chunk_size)
+                    .map(|chunk| span.in_scope(|| call_all(&self.node, chunk, arg)))
+                    .reduce(|| true, |a, b| a & b)
+            })
+        })
+    }
+
     /// Sends a value using multiple threads.
-    #[cfg(feature = "crossbeam-utils")]
-    pub fn call_parallel(&self, arg: &T)
+    ///
+    /// The callbacks are split into chunks according to `chunking`, and each chunk is run on
+    /// it's own scoped thread, then it waits for all threads to finish.
+    #[cfg(all(feature = "crossbeam-utils", not(feature = "rayon")))]
+    pub fn call_parallel(&self, arg: &T, chunking: Chunking)
     where
         T: Sync,
     {
-        let fs = self.fs.read();
-        let n = fs.len();
-        // nothing to do
-        if n == 0 {
-            return;
-        }
-        // only 1 callback, just run it on this thread
-        if n == 1 {
-            if !fs[0].call(MaybeOwned::Borrowed(arg)) {
+        trace::hop(&self.node, false).in_scope(|| {
+            self.dispatch(|fs| {
+                // 0 or 1 callbacks, just run them on this thread
+                if fs.len() < 2 {
+                    return call_all(&self.node, fs, arg);
+                }
+                let n_threads = std::thread::available_parallelism().map_or(1, usize::from);
+                let mut chunks = fs.chunks(chunking.chunk_size(fs.len(), n_threads));
+                let last = chunks.next_back().unwrap();
+                // the spawned threads continue the current span
+                let span = &trace::Span::current();
+                thread::scope(|scope| {
+                    // spawn a thread for each of the first N-1 chunks
+                    let handles: Vec<_> = chunks
+                        .map(|chunk| {
+                            scope.spawn(move |_| span.in_scope(|| call_all(&self.node, chunk, arg)))
+                        })
+                        .collect();
+                    // run the last chunk on current thread
+                    let alive = call_all(&self.node, last, arg);
+                    handles
+                        .into_iter()
+                        .fold(alive, |a, handle| a & handle.join().unwrap())
+                })
+                .unwrap()
+            })
+        })
+    }
+}
+
+#[cfg(not(feature = "arc-swap"))]
+impl<T> Callbacks<T> {
+    /// Adds a new closure to the callback list.
+    ///
+    /// If the list is being dispatched (for example, when called from inside a callback) the
+    /// closure is queued, and it will be added when the dispatch finishes.
+    pub fn push<F>(&self, cb: F)
+    where
+        F: Fn(MaybeOwned<'_, T>) -> bool + Send + Sync + 'static,
+    {
+        self.push_cell(FnCell::new(cb));
+    }
+
+    /// Removes a closure from the callback list.
+    ///
+    /// The closure won't be called again. If the list is being dispatched, it's removed when the
+    /// dispatch finishes.
+    pub fn remove(&self, handle: CallbackHandle<T>) {
+        handle.kill();
+        self.has_queued.store(true, Ordering::SeqCst);
+        self.cleanup();
+    }
+
+    fn push_cell(&self, cell: Cell<T>) {
+        if let Some(mut fs) = self.fs.try_write() {
+            fs.push(cell);
+            self.node.set_observers(fs.len());
+            drop(fs);
+            // another thread could have queued a callback while we were holding the lock
+            if self.has_queued.load(Ordering::SeqCst) {
                 self.cleanup();
             }
-            return;
+        } else {
+            self.queued.lock().push(cell);
+            self.has_queued.store(true, Ordering::SeqCst);
+            // the lock could have been released before we queued the callback
+            self.cleanup();
         }
-        // 2+ callbacks, we need more threads
-        let all_alive = AtomicBool::new(true);
-        thread::scope(|scope| {
-            let all_alive = &all_alive;
-
-            let mut i = 0;
-            // spawn N-1 threads
-            for _ in 1..n {
-                let f = &fs[i];
-                scope.spawn(move |_| {
-                    if !f.call(MaybeOwned::Borrowed(arg)) {
-                        all_alive.store(false, Ordering::Relaxed);
-                    }
-                });
-                i += 1;
+    }
+
+    /// Runs a dispatch function over the callback list.
+    ///
+    /// This is the common core of all the `call_*` methods. The dispatch function must return
+    /// `false` if any of the callbacks died, so they can be removed after the read lock is released.
+    fn dispatch<F>(&self, f: F)
+    where
+        F: FnOnce(&[Cell<T>]) -> bool,
+    {
+        self.node.record_in();
+        let all_alive = f(&self.fs.read());
+        if !all_alive || self.has_queued.load(Ordering::SeqCst) {
+            self.cleanup();
+        }
+    }
+
+    /// Removes the dead callbacks and appends the queued ones.
+    ///
+    /// This never blocks. If the list is locked, the work is left to the current lock owner.
+    fn cleanup(&self) {
+        while let Some(mut fs) = self.fs.try_write() {
+            let len = fs.len();
+            fs.retain(|f| f.is_alive());
+            self.node.record_dropped(len - fs.len());
+            if self.has_queued.swap(false, Ordering::SeqCst) {
+                fs.append(&mut self.queued.lock());
             }
-            // run the last callback on current thread
-            if !fs[i].call(MaybeOwned::Borrowed(arg)) {
-                all_alive.store(false, Ordering::Relaxed);
+            self.node.set_observers(fs.len());
+            drop(fs);
+            // someone could have queued more callbacks while we were holding the lock
+            if !self.has_queued.load(Ordering::SeqCst) {
+                break;
             }
-        })
-        .unwrap();
-        // after all threads finished, continue with the cleanup
-        drop(fs);
-        if all_alive.load(Ordering::Relaxed) {
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.fs.read().len() + self.queued.lock().len()
+    }
+}
+
+#[cfg(feature = "arc-swap")]
+impl<T> Callbacks<T> {
+    /// Adds a new closure to the callback list.
+    ///
+    /// This replaces the list with an updated copy, so a dispatch in progress will keep using the
+    /// old list and the closure will only receive the values sent after that.
+    pub fn push<F>(&self, cb: F)
+    where
+        F: Fn(MaybeOwned<'_, T>) -> bool + Send + Sync + 'static,
+    {
+        self.push_cell(FnCell::new(cb));
+    }
+
+    /// Removes a closure from the callback list.
+    ///
+    /// The closure won't be called again, even by a dispatch in progress.
+    pub fn remove(&self, handle: CallbackHandle<T>) {
+        handle.kill();
+        self.cleanup();
+    }
+
+    fn push_cell(&self, cell: Cell<T>) {
+        let fs = self.fs.rcu(|fs| {
+            let mut fs = Vec::clone(fs);
+            fs.push(cell.clone());
+            fs
+        });
+        self.node.set_observers(fs.len() + 1);
+    }
+
+    /// Runs a dispatch function over the callback list.
+    ///
+    /// This is the common core of all the `call_*` methods. The dispatch function must return
+    /// `false` if any of the callbacks died, so they can be removed after the dispatch.
+    fn dispatch<F>(&self, f: F)
+    where
+        F: FnOnce(&[Cell<T>]) -> bool,
+    {
+        self.node.record_in();
+        // callbacks can run for a long time and send values reentrantly, so we take our own
+        // reference instead of holding a load guard for the whole dispatch
+        let all_alive = f(&self.fs.load_full());
+        if !all_alive {
             self.cleanup();
         }
     }
 
     /// Removes the dead callbacks.
     fn cleanup(&self) {
-        if let Some(mut fs) = self.fs.try_write() {
-            fs.retain(FnCell::is_alive);
-        }
+        let fs = self.fs.rcu(|fs| {
+            fs.iter()
+                .filter(|f| f.is_alive())
+                .cloned()
+                .collect::<Vec<_>>()
+        });
+        let alive = fs.iter().filter(|f| f.is_alive()).count();
+        self.node.record_dropped(fs.len() - alive);
+        self.node.set_observers(alive);
     }
 
     #[cfg(test)]
     pub(crate) fn len(&self) -> usize {
-        self.fs.read().len()
+        self.fs.load().len()
     }
 }
 
-impl<T> Default for Callbacks<T> {
-    #[inline]
-    fn default() -> Self {
+#[cfg(not(feature = "arc-swap"))]
+impl<T> Callbacks<T> {
+    /// Creates an empty callback list with the supplied metadata.
+    fn with_node(node: Arc<NodeInfo>) -> Self {
+        Self {
+            node,
+            senders: Default::default(),
+            fs: Default::default(),
+            queued: Default::default(),
+            has_queued: AtomicBool::new(false),
+        }
+    }
+}
+
+#[cfg(feature = "arc-swap")]
+impl<T> Callbacks<T> {
+    /// Creates an empty callback list with the supplied metadata.
+    fn with_node(node: Arc<NodeInfo>) -> Self {
         Self {
+            node,
+            senders: Default::default(),
             fs: Default::default(),
         }
     }
 }
+
+impl<T> Default for Callbacks<T> {
+    /// Creates an empty callback list for a `Sink`.
+    #[inline]
+    fn default() -> Self {
+        Self::with_node(NodeInfo::new::<T>(NodeKind::Sink, "sink", &[]))
+    }
+}
+
+/// Sends a value by reference to a list of callbacks.
+///
+/// Returns `false` if any of the callbacks died.
+fn call_all<T>(node: &NodeInfo, fs: &[Cell<T>], arg: &T) -> bool {
+    fs.iter()
+        .map(|f| f.call(node, MaybeOwned::Borrowed(arg)))
+        .fold(true, |a, alive| a & alive)
+}
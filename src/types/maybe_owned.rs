@@ -0,0 +1,166 @@
+//! The `MaybeOwned` type received by the stream callbacks.
+
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+
+/// A value that is either owned or borrowed.
+///
+/// It's API follows the `MaybeOwned` type of the `maybe-owned` crate, but it doesn't require
+/// `std`. It replaces the re-export of that type used before version 0.5.
+pub enum MaybeOwned<'a, T: 'a> {
+    /// An owned value.
+    Owned(T),
+    /// A borrowed value.
+    Borrowed(&'a T),
+}
+
+impl<'a, T> MaybeOwned<'a, T> {
+    /// Returns `true` if the value is owned.
+    #[inline]
+    pub fn is_owned(&self) -> bool {
+        matches!(self, MaybeOwned::Owned(_))
+    }
+
+    /// Returns a mutable reference to the value if it's owned.
+    #[inline]
+    pub fn as_mut(&mut self) -> Option<&mut T> {
+        match self {
+            MaybeOwned::Owned(v) => Some(v),
+            MaybeOwned::Borrowed(_) => None,
+        }
+    }
+}
+
+impl<'a, T: Clone> MaybeOwned<'a, T> {
+    /// Returns the owned value, cloning it if it's borrowed.
+    #[inline]
+    pub fn into_owned(self) -> T {
+        match self {
+            MaybeOwned::Owned(v) => v,
+            MaybeOwned::Borrowed(r) => r.clone(),
+        }
+    }
+
+    /// Clones the value if it's borrowed, and returns a mutable reference to the owned value.
+    pub fn make_owned(&mut self) -> &mut T {
+        if let MaybeOwned::Borrowed(r) = *self {
+            *self = MaybeOwned::Owned(r.clone());
+        }
+        match self {
+            MaybeOwned::Owned(v) => v,
+            MaybeOwned::Borrowed(_) => unreachable!(),
+        }
+    }
+
+    /// Same as `make_owned`.
+    #[inline]
+    pub fn to_mut(&mut self) -> &mut T {
+        self.make_owned()
+    }
+}
+
+impl<'a, T> Deref for MaybeOwned<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        match self {
+            MaybeOwned::Owned(v) => v,
+            MaybeOwned::Borrowed(r) => r,
+        }
+    }
+}
+
+impl<'a, T> AsRef<T> for MaybeOwned<'a, T> {
+    #[inline]
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<'a, T> Borrow<T> for MaybeOwned<'a, T> {
+    #[inline]
+    fn borrow(&self) -> &T {
+        self
+    }
+}
+
+impl<'a, T: Default> Default for MaybeOwned<'a, T> {
+    #[inline]
+    fn default() -> Self {
+        MaybeOwned::Owned(T::default())
+    }
+}
+
+impl<'a, 'b, A: PartialEq<B>, B> PartialEq<MaybeOwned<'b, B>> for MaybeOwned<'a, A> {
+    #[inline]
+    fn eq(&self, other: &MaybeOwned<'b, B>) -> bool {
+        **self == **other
+    }
+}
+
+impl<'a, T: Eq> Eq for MaybeOwned<'a, T> {}
+
+impl<'a, T: PartialOrd> PartialOrd for MaybeOwned<'a, T> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<'a, T: Ord> Ord for MaybeOwned<'a, T> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<'a, T: Hash> Hash for MaybeOwned<'a, T> {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+impl<'a, T> From<T> for MaybeOwned<'a, T> {
+    #[inline]
+    fn from(val: T) -> Self {
+        MaybeOwned::Owned(val)
+    }
+}
+
+impl<'a, T> From<&'a T> for MaybeOwned<'a, T> {
+    #[inline]
+    fn from(val: &'a T) -> Self {
+        MaybeOwned::Borrowed(val)
+    }
+}
+
+impl<'a, T: Clone> Clone for MaybeOwned<'a, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        match self {
+            MaybeOwned::Owned(v) => MaybeOwned::Owned(v.clone()),
+            MaybeOwned::Borrowed(r) => MaybeOwned::Borrowed(r),
+        }
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for MaybeOwned<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MaybeOwned::Owned(v) => f.debug_tuple("Owned").field(v).finish(),
+            MaybeOwned::Borrowed(r) => f.debug_tuple("Borrowed").field(r).finish(),
+        }
+    }
+}
+
+impl<'a, T: fmt::Display> fmt::Display for MaybeOwned<'a, T> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,280 @@
+//! Metadata of the nodes in the event graph.
+//!
+//! Without `std` there is no graph registry, so most of it is only kept for the ids.
+#![cfg_attr(not(feature = "std"), allow(dead_code))]
+
+use crate::sync::Mutex;
+#[cfg(feature = "std")]
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicBool, AtomicU64};
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// The role of a node in the event graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeKind {
+    /// A `Sink`, where events enter the graph.
+    Sink,
+    /// A stream created by a stream operation.
+    Stream,
+    /// The value storage of a `Signal` created from a stream.
+    Storage,
+}
+
+/// Amount of buckets in a `LatencyHistogram`.
+pub const LATENCY_BUCKETS: usize = 24;
+
+/// Histogram of the time spent on the callbacks of a node.
+///
+/// Bucket 0 counts the calls that took less than 1µs, and bucket `i` counts the calls that took
+/// from `2^(i-1)` to `2^i` µs. The last bucket also counts all the slower calls.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    /// Amount of calls on each bucket.
+    pub buckets: [u64; LATENCY_BUCKETS],
+    /// Total time spent on the callbacks.
+    pub total: Duration,
+}
+
+impl LatencyHistogram {
+    /// Gets the upper limit of the specified bucket.
+    #[inline]
+    pub fn bucket_limit(i: usize) -> Duration {
+        Duration::from_micros(1 << i)
+    }
+
+    /// Gets the bucket where a call of the specified duration goes.
+    #[inline]
+    pub fn bucket_index(elapsed: Duration) -> usize {
+        let micros = elapsed.as_micros();
+        (128 - micros.leading_zeros() as usize).min(LATENCY_BUCKETS - 1)
+    }
+
+    /// Total amount of calls measured.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Average time spent on each call.
+    pub fn mean(&self) -> Option<Duration> {
+        match self.count() {
+            0 => None,
+            n => Some(Duration::from_nanos(
+                (self.total.as_nanos() / n as u128) as u64,
+            )),
+        }
+    }
+}
+
+/// Snapshot of the event counters of a node.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeStats {
+    /// Values that arrived at the node.
+    ///
+    /// For a stream, this is the amount of events it fired.
+    pub events_in: usize,
+    /// Values delivered from the node to it's observers.
+    ///
+    /// Each event is counted once for every observer that received it.
+    pub events_out: usize,
+    /// Dead callbacks removed from the node.
+    pub dropped: usize,
+    /// Time spent on the callbacks, if latency tracking is enabled.
+    ///
+    /// See `debug::set_latency_tracking` and `Stream::track_latency`. It's always `None` without
+    /// the `std` feature.
+    pub latency: Option<LatencyHistogram>,
+}
+
+/// Latency counters of a node.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+struct LatencyCounters {
+    buckets: [AtomicU64; LATENCY_BUCKETS],
+    total_ns: AtomicU64,
+}
+
+/// Source of unique node ids.
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// The mutable part of a node's metadata.
+#[derive(Debug)]
+struct NodeLabel {
+    operator: &'static str,
+    name: Option<Arc<str>>,
+}
+
+/// Metadata attached to a callback list or storage.
+#[derive(Debug)]
+pub struct NodeInfo {
+    id: usize,
+    kind: NodeKind,
+    value_type: &'static str,
+    parents: Vec<usize>,
+    label: Mutex<NodeLabel>,
+    observers: AtomicUsize,
+    events_in: AtomicUsize,
+    events_out: AtomicUsize,
+    dropped: AtomicUsize,
+    /// Allocated on the first measure.
+    #[cfg(feature = "std")]
+    latency: OnceLock<Box<LatencyCounters>>,
+    /// Measures this node even if global latency tracking is disabled.
+    #[cfg(feature = "std")]
+    track_latency: AtomicBool,
+}
+
+impl NodeInfo {
+    /// Creates the metadata for a node of type `T` and adds it to the graph registry.
+    pub fn new<T: ?Sized>(kind: NodeKind, operator: &'static str, parents: &[usize]) -> Arc<Self> {
+        let node = Arc::new(NodeInfo {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            kind,
+            value_type: core::any::type_name::<T>(),
+            parents: parents.to_vec(),
+            label: Mutex::new(NodeLabel {
+                operator,
+                name: None,
+            }),
+            observers: AtomicUsize::new(0),
+            events_in: AtomicUsize::new(0),
+            events_out: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+            #[cfg(feature = "std")]
+            latency: OnceLock::new(),
+            #[cfg(feature = "std")]
+            track_latency: AtomicBool::new(false),
+        });
+        #[cfg(feature = "std")]
+        crate::debug::register(&node);
+        node
+    }
+
+    /// The unique id of this node.
+    #[inline]
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The role of this node.
+    #[inline]
+    pub fn kind(&self) -> NodeKind {
+        self.kind
+    }
+
+    /// The type name of the values handled by this node.
+    #[inline]
+    pub fn value_type(&self) -> &'static str {
+        self.value_type
+    }
+
+    /// The ids of the nodes this one receives values from.
+    #[inline]
+    pub fn parents(&self) -> &[usize] {
+        &self.parents
+    }
+
+    /// The operation that created this node.
+    pub fn operator(&self) -> &'static str {
+        self.label.lock().operator
+    }
+
+    /// Replaces the operation name, used by operations implemented on top of others.
+    pub fn set_operator(&self, operator: &'static str) {
+        self.label.lock().operator = operator;
+    }
+
+    /// The user supplied name of this node.
+    pub fn name(&self) -> Option<Arc<str>> {
+        self.label.lock().name.clone()
+    }
+
+    /// Sets the user supplied name of this node.
+    pub fn set_name(&self, name: &str) {
+        self.label.lock().name = Some(name.into());
+    }
+
+    /// The amount of callbacks registered on this node.
+    #[inline]
+    pub fn observers(&self) -> usize {
+        self.observers.load(Ordering::Relaxed)
+    }
+
+    /// Updates the amount of callbacks registered on this node.
+    #[inline]
+    pub fn set_observers(&self, count: usize) {
+        self.observers.store(count, Ordering::Relaxed);
+    }
+
+    /// Counts a value arriving at this node.
+    #[inline]
+    pub fn record_in(&self) {
+        self.events_in.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a value delivered to an observer.
+    #[inline]
+    pub fn record_out(&self) {
+        self.events_out.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts the dead callbacks removed.
+    #[inline]
+    pub fn record_dropped(&self, count: usize) {
+        if count > 0 {
+            self.dropped.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    /// Enables or disables measuring the callbacks of this node.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn set_latency_tracking(&self, enabled: bool) {
+        self.track_latency.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Starts measuring a callback, if latency tracking is enabled globally or on this node.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn latency_start(&self) -> Option<Instant> {
+        if crate::debug::latency_tracking() || self.track_latency.load(Ordering::Relaxed) {
+            Some(Instant::now())
+        } else {
+            None
+        }
+    }
+
+    /// Adds the time elapsed since `start` to the latency histogram.
+    #[cfg(feature = "std")]
+    pub fn record_latency(&self, start: Instant) {
+        let elapsed = start.elapsed();
+        let latency = self.latency.get_or_init(Default::default);
+        latency.buckets[LatencyHistogram::bucket_index(elapsed)].fetch_add(1, Ordering::Relaxed);
+        latency
+            .total_ns
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Takes a snapshot of the event counters.
+    pub fn stats(&self) -> NodeStats {
+        NodeStats {
+            events_in: self.events_in.load(Ordering::Relaxed),
+            events_out: self.events_out.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            #[cfg(feature = "std")]
+            latency: self.latency.get().map(|latency| LatencyHistogram {
+                buckets: core::array::from_fn(|i| latency.buckets[i].load(Ordering::Relaxed)),
+                total: Duration::from_nanos(latency.total_ns.load(Ordering::Relaxed)),
+            }),
+            #[cfg(not(feature = "std"))]
+            latency: None,
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Bounded queue used to hand values to another thread.
+
+use crate::types::OverflowPolicy;
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+
+#[derive(Debug)]
+struct State<T> {
+    items: VecDeque<T>,
+    closed: bool,
+    /// The consumer is waiting for a value, or about to start.
+    consumer_ready: bool,
+}
+
+/// A queue with a maximum length, that handles overflow according to an `OverflowPolicy`.
+///
+/// The consumer blocks on `pop` until a value arrives or the queue is closed. When the consumer is
+/// waiting, `push` waits until it takes the value, so the values that leave the queue don't depend
+/// on how fast the consumer thread wakes up.
+#[derive(Debug)]
+pub struct BoundedQueue<T> {
+    state: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    taken: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+}
+
+impl<T> BoundedQueue<T> {
+    /// Creates an empty queue.
+    ///
+    /// The capacity is at least one.
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        BoundedQueue {
+            state: Mutex::new(State {
+                items: VecDeque::new(),
+                closed: false,
+                consumer_ready: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            taken: Condvar::new(),
+            capacity: capacity.max(1),
+            policy,
+        }
+    }
+
+    /// Creates an empty queue whose consumer is about to start.
+    ///
+    /// The first value is handed to the consumer even if it didn't reach `pop` yet.
+    pub fn with_consumer(capacity: usize, policy: OverflowPolicy) -> Self {
+        let queue = Self::new(capacity, policy);
+        queue.state.lock().unwrap().consumer_ready = true;
+        queue
+    }
+
+    /// Adds a value to the queue.
+    ///
+    /// Returns `false` if the queue was closed.
+    pub fn push(&self, val: T) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.items.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::Block => {
+                    while state.items.len() >= self.capacity && !state.closed {
+                        state = self.not_full.wait(state).unwrap();
+                    }
+                }
+                OverflowPolicy::DropNewest => return !state.closed,
+                OverflowPolicy::DropOldest => {
+                    state.items.pop_front();
+                }
+                OverflowPolicy::CoalesceLatest if !state.closed => {
+                    if let Some(last) = state.items.back_mut() {
+                        *last = val;
+                    }
+                    return true;
+                }
+                OverflowPolicy::CoalesceLatest => return false,
+            }
+        }
+        if state.closed {
+            return false;
+        }
+        state.items.push_back(val);
+        self.not_empty.notify_one();
+        // hand the value to the waiting consumer
+        while state.consumer_ready && !state.items.is_empty() && !state.closed {
+            state = self.taken.wait(state).unwrap();
+        }
+        true
+    }
+
+    /// Takes the oldest value, waiting until there is one.
+    ///
+    /// Returns `None` when the queue is closed and empty.
+    pub fn pop(&self) -> Option<T> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(val) = state.items.pop_front() {
+                state.consumer_ready = false;
+                self.not_full.notify_one();
+                self.taken.notify_all();
+                return Some(val);
+            }
+            if state.closed {
+                return None;
+            }
+            state.consumer_ready = true;
+            state = self.not_empty.wait(state).unwrap();
+        }
+    }
+
+    /// Closes the queue.
+    ///
+    /// The values already in the queue can still be taken, but new ones are rejected.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+        self.taken.notify_all();
+    }
+
+    /// Gets the amount of values in the queue.
+    pub fn len(&self) -> usize {
+        self.state.lock().unwrap().items.len()
+    }
+}
+
+/// The producer side of a queue. It closes the queue when dropped.
+#[derive(Debug)]
+pub struct QueueWriter<T>(Arc<BoundedQueue<T>>);
+
+impl<T> QueueWriter<T> {
+    /// Creates the producer for a queue.
+    #[inline]
+    pub fn new(queue: Arc<BoundedQueue<T>>) -> Self {
+        QueueWriter(queue)
+    }
+
+    /// Adds a value to the queue.
+    #[inline]
+    pub fn push(&self, val: T) -> bool {
+        self.0.push(val)
+    }
+}
+
+impl<T> Drop for QueueWriter<T> {
+    fn drop(&mut self) {
+        self.0.close()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn queue_policies() {
+        let q = BoundedQueue::new(2, OverflowPolicy::DropNewest);
+        assert!(q.push(1) && q.push(2) && q.push(3));
+        assert_eq!((q.pop(), q.pop()), (Some(1), Some(2)));
+
+        let q = BoundedQueue::new(2, OverflowPolicy::DropOldest);
+        assert!(q.push(1) && q.push(2) && q.push(3));
+        assert_eq!((q.pop(), q.pop()), (Some(2), Some(3)));
+
+        let q = BoundedQueue::new(2, OverflowPolicy::CoalesceLatest);
+        assert!(q.push(1) && q.push(2) && q.push(3) && q.push(4));
+        assert_eq!(q.len(), 2);
+        assert_eq!((q.pop(), q.pop()), (Some(1), Some(4)));
+
+        let q = Arc::new(BoundedQueue::new(1, OverflowPolicy::Block));
+        let writer = QueueWriter::new(q.clone());
+        let t = thread::spawn(move || {
+            // the second push waits until the first value is taken
+            writer.push(1);
+            writer.push(2);
+        });
+        assert_eq!(q.pop(), Some(1));
+        assert_eq!(q.pop(), Some(2));
+        t.join().unwrap();
+        // the writer closed the queue when dropped
+        assert_eq!(q.pop(), None);
+        assert!(!q.push(3));
+    }
+}
@@ -1,10 +1,22 @@
 //! Storage cell used by Signal.
 
+use crate::signal::SampleError;
 use crate::sync::RwLock;
+use crate::types::{NodeInfo, NodeKind};
+use alloc::sync::Arc;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 /// Storage cell for shared signal values.
 pub struct Storage<T> {
     val: RwLock<Option<T>>,
+    /// Set while a closure updates the value, so it stays set if the closure panics.
+    ///
+    /// It's only accessed with the lock held, so it works the same with every lock backend.
+    poisoned: AtomicBool,
+    /// Keeps the storage registered in the event graph.
+    #[allow(dead_code)]
+    node: Option<Arc<NodeInfo>>,
 }
 
 const ERR_EMPTY: &str = "storage empty";
@@ -14,43 +26,101 @@ impl<T> Storage<T> {
     pub fn new(val: T) -> Self {
         Storage {
             val: RwLock::new(Some(val)),
+            poisoned: AtomicBool::new(false),
+            node: None,
         }
     }
 
-    /// Gets the value by cloning.
-    pub fn get(&self) -> T
+    /// Creates a storage that is visible in the event graph.
+    pub fn with_node(val: T, operator: &'static str, parent: usize) -> Self {
+        Storage {
+            val: RwLock::new(Some(val)),
+            poisoned: AtomicBool::new(false),
+            node: Some(NodeInfo::new::<T>(NodeKind::Storage, operator, &[parent])),
+        }
+    }
+
+    /// Gets the value by cloning, or an error if the storage is empty or poisoned.
+    pub fn try_get(&self) -> Result<T, SampleError>
     where
         T: Clone,
     {
-        self.val.read().clone().expect(ERR_EMPTY)
+        let guard = self.val.read();
+        self.check_poison()?;
+        guard.clone().ok_or(SampleError::Empty)
+    }
+
+    /// Borrows the value, or returns an error if the storage is empty or poisoned.
+    ///
+    /// The storage stays read-locked until the returned reference is dropped.
+    pub fn try_borrow(&self) -> Result<impl Deref<Target = T> + '_, SampleError> {
+        let guard = self.val.read();
+        self.check_poison()?;
+        match *guard {
+            Some(_) => Ok(Borrowed(guard)),
+            None => Err(SampleError::Empty),
+        }
     }
 
     /// Sets the value.
+    ///
+    /// This also recovers a storage that was left empty or poisoned by a panic.
     pub fn set(&self, val: T) {
-        *self.val.write() = Some(val);
+        let mut st = self.val.write();
+        *st = Some(val);
+        self.poisoned.store(false, Ordering::Relaxed);
     }
 
     /// Maps the stored value in place.
+    ///
+    /// Does nothing if the storage was left empty by a panic.
     pub fn replace<F>(&self, f: F)
     where
         F: FnOnce(T) -> T,
     {
-        let mut st = self.val.write();
-        let old = st.take().expect(ERR_EMPTY);
-        *st = Some(f(old));
+        let _ = self.try_replace_with(|old| (f(old), ()));
     }
 
-    /// Same as `replace` but it also returns the new value.
-    pub fn replace_fetch<F>(&self, f: F) -> T
+    /// Same as `replace` but it also returns the new value, or an error if the storage is empty
+    /// or poisoned.
+    pub fn try_replace_fetch<F>(&self, f: F) -> Result<T, SampleError>
     where
         F: FnOnce(T) -> T,
         T: Clone,
+    {
+        self.try_replace_with(|old| {
+            let new = f(old);
+            (new.clone(), new)
+        })
+    }
+
+    /// Maps the stored value in place and returns the extra output of the closure, or an error if
+    /// the storage is empty or poisoned.
+    pub fn try_replace_with<F, R>(&self, f: F) -> Result<R, SampleError>
+    where
+        F: FnOnce(T) -> (T, R),
     {
         let mut st = self.val.write();
-        let old = st.take().expect(ERR_EMPTY);
-        let new = f(old);
-        *st = Some(new.clone());
-        new
+        self.check_poison()?;
+        let old = st.take().ok_or(SampleError::Empty)?;
+        // stays set if `f` panics, since the old value is lost
+        self.poisoned.store(true, Ordering::Relaxed);
+        let (new, out) = f(old);
+        self.poisoned.store(false, Ordering::Relaxed);
+        *st = Some(new);
+        Ok(out)
+    }
+
+    /// Returns an error if a closure panicked while updating the value.
+    ///
+    /// Must be called with the lock held.
+    #[inline]
+    fn check_poison(&self) -> Result<(), SampleError> {
+        if self.poisoned.load(Ordering::Relaxed) {
+            Err(SampleError::Poisoned)
+        } else {
+            Ok(())
+        }
     }
 
     /// A `replace` version with cloning.
@@ -60,8 +130,9 @@ impl<T> Storage<T> {
         T: Clone,
     {
         let mut st = self.val.write();
-        let old = st.clone().expect(ERR_EMPTY);
-        *st = Some(f(old));
+        if let Some(old) = st.clone() {
+            *st = Some(f(old));
+        }
     }
 }
 
@@ -70,6 +141,20 @@ impl<T> Default for Storage<T> {
     fn default() -> Self {
         Storage {
             val: Default::default(),
+            poisoned: AtomicBool::new(false),
+            node: None,
         }
     }
 }
+
+/// A read guard of a storage that is known to contain a value.
+struct Borrowed<G>(G);
+
+impl<T, G: Deref<Target = Option<T>>> Deref for Borrowed<G> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.0.as_ref().expect(ERR_EMPTY)
+    }
+}